@@ -0,0 +1,84 @@
+#ifndef MATRIX_PROFILE_H
+#define MATRIX_PROFILE_H
+
+/* Generated with cbindgen from src/lib.rs, don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Error codes of the functions.
+typedef enum MpError {
+  MP_ERROR_OK = 0,
+  // A required pointer was null.
+  MP_ERROR_NULL_POINTER = 1,
+  // The window is smaller than 2 or longer than the series.
+  MP_ERROR_INVALID_WINDOW = 2,
+  // Unexpected internal error.
+  MP_ERROR_PANIC = 3,
+} MpError;
+
+// Matrix profile updated as values arrive, created with `mp_stream_new`.
+typedef struct MpStream MpStream;
+
+// Matrix profile with `len` subsequences, allocated by the library.
+typedef struct MpProfile {
+  // Distance of each subsequence to its nearest neighbour, infinite without neighbour.
+  float *profile;
+  // Index of the nearest neighbour of each subsequence, -1 without neighbour.
+  int64_t *profile_idxs;
+  size_t len;
+  // Window size.
+  size_t m;
+  // Nearby subsequences are not neighbours when they are at most this far apart.
+  size_t exclusion_zone;
+} MpProfile;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Human readable description of an error code, as a static null terminated string.
+//
+// The code is taken as an `int` so that values outside of [`MpError`] are not undefined
+// behaviour, they are described as unknown errors.
+const char *mp_error_message(int error);
+
+// Computes the matrix profile of a series with the STOMP algorithm.
+//
+// Missing values can be represented as NaN or infinite. On success `out` holds the profile,
+// which must be released with `mp_free`.
+//
+// # Arguments
+//
+// * `x` - Time series data, `len` values
+// * `m` - Window size
+// * `out` - Receives the profile
+enum MpError mp_compute(const float *x, size_t len, size_t m, struct MpProfile *out);
+
+// Releases the buffers of a profile, which is left empty. Releasing an empty profile does
+// nothing.
+void mp_free(struct MpProfile *profile);
+
+// Creates an empty streaming profile with window `m`, released with `mp_stream_free`.
+//
+// Returns null when the window is smaller than 2.
+struct MpStream *mp_stream_new(size_t m);
+
+// Adds `len` values to a streaming profile, updating it.
+enum MpError mp_stream_push(struct MpStream *stream, const float *values, size_t len);
+
+// Copies the current profile of a stream into `out`, which must be released with `mp_free`.
+// The profile is empty until `m` values are received.
+enum MpError mp_stream_profile(const struct MpStream *stream, struct MpProfile *out);
+
+// Releases a streaming profile, null pointers are ignored.
+void mp_stream_free(struct MpStream *stream);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MATRIX_PROFILE_H */
@@ -0,0 +1,296 @@
+//! C ABI of the `matrix_profile` crate, to embed it in C and C++ programs.
+//!
+//! The header `include/matrix_profile.h` is generated with [cbindgen] from this file:
+//!
+//! ```text
+//! cbindgen --config cbindgen.toml --output include/matrix_profile.h
+//! ```
+//!
+//! Every function returns an [`MpError`] code, panics are caught and reported as
+//! [`MpError::Panic`]. Profiles are returned in [`MpProfile`] structs owned by the caller, which
+//! must be released with `mp_free`.
+//!
+//! [cbindgen]: https://github.com/mozilla/cbindgen
+#![allow(clippy::missing_safety_doc)]
+use matrix_profile::{MatrixProfile, StompWorkspace, StreamingMatrixProfile, NO_NEIGHBOUR};
+use std::{
+    ffi::{c_char, c_int},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// Error codes of the functions.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MpError {
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer = 1,
+    /// The window is smaller than 2 or longer than the series.
+    InvalidWindow = 2,
+    /// Unexpected internal error.
+    Panic = 3,
+}
+
+/// Matrix profile with `len` subsequences, allocated by the library.
+#[repr(C)]
+pub struct MpProfile {
+    /// Distance of each subsequence to its nearest neighbour, infinite without neighbour.
+    pub profile: *mut f32,
+    /// Index of the nearest neighbour of each subsequence, -1 without neighbour.
+    pub profile_idxs: *mut i64,
+    pub len: usize,
+    /// Window size.
+    pub m: usize,
+    /// Nearby subsequences are not neighbours when they are at most this far apart.
+    pub exclusion_zone: usize,
+}
+
+/// Matrix profile updated as values arrive, created with `mp_stream_new`.
+pub struct MpStream {
+    inner: StreamingMatrixProfile,
+}
+
+impl MpProfile {
+    fn empty() -> Self {
+        Self {
+            profile: ptr::null_mut(),
+            profile_idxs: ptr::null_mut(),
+            len: 0,
+            m: 0,
+            exclusion_zone: 0,
+        }
+    }
+
+    fn from_profile<T: MatrixProfile>(mp: &T) -> Self {
+        let profile = mp.get_profile().clone().into_boxed_slice();
+        let profile_idxs = mp
+            .get_profile_idxs()
+            .iter()
+            .map(|&o| if o == NO_NEIGHBOUR { -1 } else { o as i64 })
+            .collect::<Box<[_]>>();
+        Self {
+            len: profile.len(),
+            profile: Box::into_raw(profile) as *mut f32,
+            profile_idxs: Box::into_raw(profile_idxs) as *mut i64,
+            m: mp.get_window_size(),
+            exclusion_zone: mp.get_exclusion_zone(),
+        }
+    }
+}
+
+/// Runs `f` reporting panics as [`MpError::Panic`].
+fn guard(f: impl FnOnce() -> MpError) -> MpError {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(MpError::Panic)
+}
+
+/// Human readable description of an error code, as a static null terminated string.
+///
+/// The code is taken as an `int` so that values outside of [`MpError`] are not undefined
+/// behaviour, they are described as unknown errors.
+#[no_mangle]
+pub extern "C" fn mp_error_message(error: c_int) -> *const c_char {
+    let message: &'static [u8] = match error {
+        o if o == MpError::Ok as c_int => b"no error\0",
+        o if o == MpError::NullPointer as c_int => b"a required pointer is null\0",
+        o if o == MpError::InvalidWindow as c_int => {
+            b"the window must be between 2 and the series length\0"
+        }
+        o if o == MpError::Panic as c_int => b"internal error\0",
+        _ => b"unknown error\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// Computes the matrix profile of a series with the STOMP algorithm.
+///
+/// Missing values can be represented as NaN or infinite. On success `out` holds the profile,
+/// which must be released with `mp_free`.
+///
+/// # Arguments
+///
+/// * `x` - Time series data, `len` values
+/// * `m` - Window size
+/// * `out` - Receives the profile
+#[no_mangle]
+pub unsafe extern "C" fn mp_compute(
+    x: *const f32,
+    len: usize,
+    m: usize,
+    out: *mut MpProfile,
+) -> MpError {
+    if x.is_null() || out.is_null() {
+        return MpError::NullPointer;
+    }
+    *out = MpProfile::empty();
+    if m < 2 || m > len {
+        return MpError::InvalidWindow;
+    }
+    let x = slice::from_raw_parts(x, len);
+    guard(|| {
+        let mp = StompWorkspace::new().calculate(x, m);
+        *out = MpProfile::from_profile(&mp);
+        MpError::Ok
+    })
+}
+
+/// Releases the buffers of a profile, which is left empty. Releasing an empty profile does
+/// nothing.
+#[no_mangle]
+pub unsafe extern "C" fn mp_free(profile: *mut MpProfile) {
+    let Some(profile) = profile.as_mut() else {
+        return;
+    };
+    if !profile.profile.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            profile.profile,
+            profile.len,
+        )));
+    }
+    if !profile.profile_idxs.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            profile.profile_idxs,
+            profile.len,
+        )));
+    }
+    *profile = MpProfile::empty();
+}
+
+/// Creates an empty streaming profile with window `m`, released with `mp_stream_free`.
+///
+/// Returns null when the window is smaller than 2.
+#[no_mangle]
+pub extern "C" fn mp_stream_new(m: usize) -> *mut MpStream {
+    if m < 2 {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(MpStream {
+        inner: StreamingMatrixProfile::new(m),
+    }))
+}
+
+/// Adds `len` values to a streaming profile, updating it.
+#[no_mangle]
+pub unsafe extern "C" fn mp_stream_push(
+    stream: *mut MpStream,
+    values: *const f32,
+    len: usize,
+) -> MpError {
+    let Some(stream) = stream.as_mut() else {
+        return MpError::NullPointer;
+    };
+    if values.is_null() && len > 0 {
+        return MpError::NullPointer;
+    }
+    if len == 0 {
+        return MpError::Ok;
+    }
+    let values = slice::from_raw_parts(values, len);
+    guard(|| {
+        stream.inner.extend(values.iter().cloned());
+        MpError::Ok
+    })
+}
+
+/// Copies the current profile of a stream into `out`, which must be released with `mp_free`.
+/// The profile is empty until `m` values are received.
+#[no_mangle]
+pub unsafe extern "C" fn mp_stream_profile(
+    stream: *const MpStream,
+    out: *mut MpProfile,
+) -> MpError {
+    let (Some(stream), false) = (stream.as_ref(), out.is_null()) else {
+        return MpError::NullPointer;
+    };
+    guard(|| {
+        *out = MpProfile::from_profile(&stream.inner);
+        MpError::Ok
+    })
+}
+
+/// Releases a streaming profile, null pointers are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn mp_stream_free(stream: *mut MpStream) {
+    if !stream.is_null() {
+        drop(Box::from_raw(stream));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matrix_profile::StompMatrixProfile;
+    use std::ffi::CStr;
+
+    /// Sine wave with noise from a linear congruential generator, to avoid ties.
+    fn series() -> Vec<f32> {
+        let mut state = 34u32;
+        (0..200)
+            .map(|i| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (i as f32 / 5.0).sin() + (state >> 8) as f32 / (1 << 24) as f32 * 0.2
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compute() {
+        let x = series();
+        let expected = StompMatrixProfile::calculate(x.clone(), 16);
+        let mut res = MpProfile::empty();
+        unsafe {
+            assert_eq!(mp_compute(x.as_ptr(), x.len(), 16, &mut res), MpError::Ok);
+            assert_eq!(res.len, 185);
+            assert_eq!((res.m, res.exclusion_zone), (16, 4));
+            let profile = slice::from_raw_parts(res.profile, res.len);
+            let idxs = slice::from_raw_parts(res.profile_idxs, res.len);
+            assert_eq!(profile, expected.get_profile().as_slice());
+            assert_eq!(idxs[0] as usize, expected.get_profile_idxs()[0]);
+            mp_free(&mut res);
+            assert!(res.profile.is_null());
+            mp_free(&mut res);
+
+            assert_eq!(
+                mp_compute(x.as_ptr(), x.len(), 1, &mut res),
+                MpError::InvalidWindow
+            );
+            assert_eq!(
+                mp_compute(ptr::null(), 0, 16, &mut res),
+                MpError::NullPointer
+            );
+            let message = CStr::from_ptr(mp_error_message(MpError::InvalidWindow as c_int));
+            assert!(message.to_str().unwrap().contains("window"));
+            for &code in [-1, 4, c_int::MAX].iter() {
+                let message = CStr::from_ptr(mp_error_message(code));
+                assert_eq!(message.to_str().unwrap(), "unknown error");
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let x = series();
+        let expected = StompMatrixProfile::calculate(x.clone(), 16);
+        unsafe {
+            let stream = mp_stream_new(16);
+            assert_eq!(mp_stream_push(stream, x.as_ptr(), 100), MpError::Ok);
+            assert_eq!(mp_stream_push(stream, x[100..].as_ptr(), 100), MpError::Ok);
+            let mut res = MpProfile::empty();
+            assert_eq!(mp_stream_profile(stream, &mut res), MpError::Ok);
+            let idxs = slice::from_raw_parts(res.profile_idxs, res.len);
+            let expected_idxs = expected
+                .get_profile_idxs()
+                .iter()
+                .map(|&o| o as i64)
+                .collect::<Vec<_>>();
+            assert_eq!(idxs, expected_idxs.as_slice());
+            mp_free(&mut res);
+            mp_stream_free(stream);
+            assert!(mp_stream_new(1).is_null());
+            assert_eq!(
+                mp_stream_push(ptr::null_mut(), x.as_ptr(), 1),
+                MpError::NullPointer
+            );
+        }
+    }
+}
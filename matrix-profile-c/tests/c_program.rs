@@ -0,0 +1,37 @@
+//! Compiles `tests/test_matrix_profile.c` against the generated header and the shared library,
+//! and runs it.
+use std::{env, path::PathBuf, process::Command};
+
+#[test]
+fn test_c_program() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // The integration tests run from `target/<profile>/deps`, next to the shared library
+    let lib_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let exe = lib_dir.join("test_matrix_profile_c");
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(&cc)
+        .arg(root.join("tests/test_matrix_profile.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lmatrix_profile_c", "-lm", "-Wall", "-Werror", "-o"])
+        .arg(&exe)
+        .status()
+        .expect("A C compiler is required, set it with the CC environment variable");
+    assert!(status.success(), "Compiling the C program failed");
+
+    // Cargo puts `target/<profile>` first in the library path, where the library may be stale
+    let output = Command::new(&exe)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("window"));
+}
@@ -0,0 +1,66 @@
+/* Links against the library and checks the results of the C ABI. */
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "matrix_profile.h"
+
+#define N 300
+#define M 20
+
+#define CHECK(cond)                                                     \
+  do {                                                                  \
+    if (!(cond)) {                                                      \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      return 1;                                                         \
+    }                                                                   \
+  } while (0)
+
+int main(void) {
+  /* Sine wave with noise from a linear congruential generator and an anomaly */
+  float x[N];
+  uint32_t state = 34;
+  for (int i = 0; i < N; i++) {
+    state = state * 1664525u + 1013904223u;
+    x[i] = sinf(i / 5.0f) + (state >> 8) / 16777216.0f * 0.2f;
+    x[i] += i >= 200 && i < 205 ? 2.0f : 0.0f;
+  }
+  x[50] = NAN;
+
+  MpProfile profile;
+  CHECK(mp_compute(x, N, M, &profile) == MP_ERROR_OK);
+  CHECK(profile.len == N - M + 1);
+  CHECK(profile.m == M && profile.exclusion_zone == 5);
+  /* Subsequences touching the missing value have no neighbour */
+  CHECK(isinf(profile.profile[40]) && profile.profile_idxs[40] == -1);
+  /* The anomaly is the farthest subsequence from its neighbour */
+  size_t discord = 0;
+  for (size_t i = 0; i < profile.len; i++) {
+    if (isfinite(profile.profile[i]) && profile.profile[i] > profile.profile[discord]) {
+      discord = i;
+    }
+  }
+  CHECK(discord > 180 && discord < 205);
+
+  /* The streaming profile ends with the same indices */
+  MpStream *stream = mp_stream_new(M);
+  CHECK(stream != NULL);
+  CHECK(mp_stream_push(stream, x, 100) == MP_ERROR_OK);
+  CHECK(mp_stream_push(stream, x + 100, N - 100) == MP_ERROR_OK);
+  MpProfile streamed;
+  CHECK(mp_stream_profile(stream, &streamed) == MP_ERROR_OK);
+  CHECK(streamed.len == profile.len);
+  for (size_t i = 0; i < profile.len; i++) {
+    CHECK(streamed.profile_idxs[i] == profile.profile_idxs[i]);
+  }
+  mp_free(&streamed);
+  mp_stream_free(stream);
+  mp_free(&profile);
+  CHECK(profile.profile == NULL && profile.len == 0);
+
+  CHECK(mp_compute(x, N, 1, &profile) == MP_ERROR_INVALID_WINDOW);
+  CHECK(mp_compute(NULL, N, M, &profile) == MP_ERROR_NULL_POINTER);
+  CHECK(strcmp(mp_error_message(42), "unknown error") == 0);
+  printf("%s\n", mp_error_message(MP_ERROR_INVALID_WINDOW));
+  return 0;
+}
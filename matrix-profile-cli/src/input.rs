@@ -0,0 +1,223 @@
+//! Reads a time series from CSV, TSV, plain-number or NumPy `.npy` files, and Arrow IPC or
+//! Parquet files with the `arrow` feature.
+#[cfg(feature = "arrow")]
+use matrix_profile::arrow_io::{read_ipc, read_parquet, ColumnSeries};
+use matrix_profile::npy::NpyArray;
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Reads the series from `path` (`-` reads stdin), see [`parse_series`].
+pub fn read_series(path: &Path, column: Option<&str>) -> Result<Vec<f32>, Box<dyn Error>> {
+    #[cfg(feature = "arrow")]
+    {
+        if is_table(path) {
+            return Ok(read_table(path, column, None)?.values);
+        }
+    }
+    if path.extension().is_some_and(|o| o == "npy") {
+        return read_npy(path, column);
+    }
+    let text = if path.as_os_str() == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+    let series = parse_series(&text, column)?;
+    Ok(series)
+}
+
+/// Whether the file is read as an Arrow IPC or Parquet table, by its extension.
+#[cfg(feature = "arrow")]
+pub fn is_table(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|o| o.to_str()),
+        Some("parquet") | Some("arrow") | Some("arrows") | Some("ipc") | Some("feather")
+    )
+}
+
+/// Reads a series from an Arrow IPC or Parquet file, the `column` must be given by name.
+#[cfg(feature = "arrow")]
+pub fn read_table(
+    path: &Path,
+    column: Option<&str>,
+    timestamp_column: Option<&str>,
+) -> Result<ColumnSeries, Box<dyn Error>> {
+    let column = column.ok_or("The column is required for Arrow and Parquet input")?;
+    let res = if path.extension().is_some_and(|o| o == "parquet") {
+        read_parquet(path, column, timestamp_column)
+    } else {
+        read_ipc(path, column, timestamp_column)
+    };
+    Ok(res.map_err(|e| format!("{}: {}", path.display(), e))?)
+}
+
+/// Reads a one dimensional array, or a column (by position) of a two dimensional one.
+fn read_npy(path: &Path, column: Option<&str>) -> Result<Vec<f32>, Box<dyn Error>> {
+    let array = NpyArray::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let values = array.to_f32();
+    match (array.shape.as_slice(), column) {
+        ([_], None) | ([_], Some("0")) => Ok(values),
+        ([_, cols], column) => {
+            let idx = match column {
+                Some(column) => column
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|o| o < cols)
+                    .ok_or_else(|| format!("Column '{}' not found", column))?,
+                None => 0,
+            };
+            Ok(values.into_iter().skip(idx).step_by(*cols).collect())
+        }
+        ([_], Some(_)) => Err("The array only has one column".into()),
+        (shape, _) => Err(format!("Arrays with shape {:?} are not supported", shape).into()),
+    }
+}
+
+/// Parses a series, the format is detected from the first line:
+///
+/// - Tab separated values if it contains tabs.
+/// - Comma separated values if it contains commas.
+/// - Otherwise plain numbers separated by whitespace.
+///
+/// Delimited files may have a header, the `column` is chosen by name or position (starting at 0),
+/// by default the first numeric column is used. The first row is a header when it names the
+/// column or its value in the column is not a number. Empty, `NaN` and `NA` values are missing
+/// values.
+pub fn parse_series(text: &str, column: Option<&str>) -> Result<Vec<f32>, String> {
+    let first_line = text.lines().find(|o| !o.trim().is_empty()).unwrap_or("");
+    let delimiter = if first_line.contains('\t') {
+        b'\t'
+    } else if first_line.contains(',') {
+        b','
+    } else {
+        if column.is_some() && column != Some("0") {
+            return Err("Plain number input only has one column".to_string());
+        }
+        return text.split_whitespace().map(parse_value).collect();
+    };
+
+    let mut records = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .from_reader(text.as_bytes())
+        .into_records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    records.retain(|o| o.iter().any(|field| !field.trim().is_empty()));
+    let first = records.first().ok_or("The input is empty")?;
+    // A column named in the first row makes it a header, numbers are positions
+    let named = column
+        .filter(|o| o.parse::<usize>().is_err())
+        .and_then(|column| first.iter().position(|name| name.trim() == column));
+    let idx = match (column, named) {
+        (_, Some(idx)) => idx,
+        (Some(column), None) => column
+            .parse::<usize>()
+            .map_err(|_| format!("Column '{}' not found", column))?,
+        (None, None) => {
+            // The first row may be a header, so the numeric columns are found on the next one
+            let probe = records.get(1).unwrap_or(first);
+            (0..probe.len())
+                .find(|&i| {
+                    probe
+                        .get(i)
+                        .is_some_and(|o| !o.trim().is_empty() && parse_value(o).is_ok())
+                })
+                .ok_or("No numeric column found")?
+        }
+    };
+    // Only the selected column decides if the first row is a header, other columns (e.g. dates)
+    // never parse as numbers
+    let header = named.is_some() || first.get(idx).is_some_and(|o| parse_value(o).is_err());
+    let data = &records[header as usize..];
+
+    data.iter()
+        .enumerate()
+        .map(|(i, record)| {
+            let value = record
+                .get(idx)
+                .ok_or_else(|| format!("Row {} has no column {}", i + 1, idx))?;
+            parse_value(value)
+        })
+        .collect()
+}
+
+fn parse_value(value: &str) -> Result<f32, String> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("nan") || value.eq_ignore_ascii_case("na") {
+        return Ok(f32::NAN);
+    }
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matrix_profile::npy::NpyData;
+
+    #[test]
+    fn test_plain() {
+        let res = parse_series("1.5\n2\n\n3 4\n-1e2\n", None).unwrap();
+        assert_eq!(res, vec![1.5, 2.0, 3.0, 4.0, -100.0]);
+        assert!(parse_series("1\n2\n", Some("value")).is_err());
+        assert!(parse_series("1\nabc\n", None).is_err());
+    }
+
+    #[test]
+    fn test_csv_columns() {
+        let text = "time,value,other\n2021-01-01,1.0,5\n2021-01-02,,6\n2021-01-03,3.0,7\n";
+        let res = parse_series(text, None).unwrap();
+        assert_eq!(res[0], 1.0);
+        assert!(res[1].is_nan());
+        assert_eq!(res[2], 3.0);
+        assert_eq!(
+            parse_series(text, Some("other")).unwrap(),
+            vec![5.0, 6.0, 7.0]
+        );
+        assert_eq!(parse_series(text, Some("2")).unwrap(), vec![5.0, 6.0, 7.0]);
+        assert!(parse_series(text, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_npy() {
+        let path = std::env::temp_dir().join("matrix_profile_cli_test.npy");
+        NpyArray::new(
+            vec![3, 2],
+            NpyData::F64(vec![1.0, 10.0, 2.0, 20.0, 3.0, 30.0]),
+        )
+        .save(&path)
+        .unwrap();
+        assert_eq!(read_series(&path, None).unwrap(), vec![1.0, 2.0, 3.0]);
+        assert_eq!(
+            read_series(&path, Some("1")).unwrap(),
+            vec![10.0, 20.0, 30.0]
+        );
+        assert!(read_series(&path, Some("2")).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_csv_dates_without_header() {
+        let text = "2021-01-01,1.0\n2021-01-02,2.0\n2021-01-03,3.0\n";
+        assert_eq!(parse_series(text, None).unwrap(), vec![1.0, 2.0, 3.0]);
+        assert_eq!(parse_series(text, Some("1")).unwrap(), vec![1.0, 2.0, 3.0]);
+        assert!(parse_series(text, Some("0")).is_err());
+        let res = parse_series("time,value\n2021-01-01,1.0\n", Some("1")).unwrap();
+        assert_eq!(res, vec![1.0]);
+    }
+
+    #[test]
+    fn test_tsv_without_header() {
+        let res = parse_series("1\t10\n2\t20\n3\tNaN\n", Some("1")).unwrap();
+        assert_eq!(res[..2], [10.0, 20.0]);
+        assert!(res[2].is_nan());
+    }
+}
@@ -0,0 +1,347 @@
+//! Command line tool to compute matrix profiles of time series stored in files.
+mod input;
+mod output;
+
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand, ValueEnum};
+use input::read_series;
+use matrix_profile::{
+    motifs::{top_discords, top_motifs},
+    segmentation::{corrected_arc_curve, regime_changes},
+    DampDiscords, JoinMatrixProfile, MatrixProfile, NaiveMatrixProfile, StompMatrixProfile,
+};
+use output::{Format, Table, Value};
+use std::{error::Error, fs::File, io, path::PathBuf, process};
+
+#[derive(Parser)]
+#[command(name = "matrix-profile", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Computes the matrix profile and its indices
+    Profile {
+        #[command(flatten)]
+        input: InputArgs,
+        #[arg(short, long, value_enum, default_value_t = Algorithm::Stomp)]
+        algorithm: Algorithm,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Finds the closest pairs of subsequences
+    Motifs {
+        #[command(flatten)]
+        input: InputArgs,
+        #[arg(short, long, value_enum, default_value_t = Algorithm::Stomp)]
+        algorithm: Algorithm,
+        /// Number of motifs
+        #[arg(short, default_value_t = 3, value_parser = at_least_one())]
+        k: usize,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Finds the subsequences farthest from their nearest neighbour
+    Discords {
+        #[command(flatten)]
+        input: InputArgs,
+        #[arg(short, long, value_enum, default_value_t = DiscordAlgorithm::Stomp)]
+        algorithm: DiscordAlgorithm,
+        /// Number of discords
+        #[arg(short, default_value_t = 3, value_parser = at_least_one())]
+        k: usize,
+        /// Start of the search with DAMP, the data before is only used as reference (defaults to
+        /// a quarter of the series)
+        #[arg(long)]
+        split: Option<usize>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Finds the nearest neighbour of each subsequence of the input in another series
+    Join {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Series where the neighbours are searched, same formats as the input
+        #[arg(long)]
+        other: PathBuf,
+        /// Column of the other series, by name or position
+        #[arg(long)]
+        other_column: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Finds regime changes with the FLUSS algorithm
+    Segment {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Number of regime changes
+        #[arg(short, default_value_t = 1, value_parser = at_least_one())]
+        k: usize,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// CSV, TSV, plain numbers or NumPy .npy file, `-` reads from stdin. With the `arrow` feature
+    /// also Arrow IPC (.arrow, .arrows, .ipc, .feather) and Parquet (.parquet) files
+    input: PathBuf,
+    /// Column to use, by name or position starting at 0 (defaults to the first numeric column)
+    #[arg(short, long)]
+    column: Option<String>,
+    /// Column with the timestamps of Arrow IPC and Parquet inputs, added to the Parquet profile
+    #[cfg(feature = "arrow")]
+    #[arg(long)]
+    timestamp_column: Option<String>,
+    /// Window size
+    #[arg(short = 'm', long)]
+    window: usize,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    #[arg(short, long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+    /// Output file, stdout by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Algorithm {
+    Stomp,
+    /// Brute force, only for small series
+    Naive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum DiscordAlgorithm {
+    Stomp,
+    Naive,
+    /// Left matrix profile discords without computing the full profile
+    Damp,
+}
+
+/// Parser of the number of results to find, which must be at least one.
+fn at_least_one() -> RangedU64ValueParser<usize> {
+    RangedU64ValueParser::new().range(1..)
+}
+
+impl InputArgs {
+    /// Reads the series, checking it is long enough for the window.
+    fn read(&self) -> Result<Vec<f32>, Box<dyn Error>> {
+        let x = read_series(&self.input, self.column.as_deref())?;
+        check_window(&x, self.window)?;
+        Ok(x)
+    }
+}
+
+impl OutputArgs {
+    fn write(&self, table: &Table) -> Result<(), Box<dyn Error>> {
+        match &self.output {
+            Some(path) => table.write(File::create(path)?, self.format),
+            None => table.write(io::stdout().lock(), self.format),
+        }
+    }
+}
+
+fn check_window(x: &[f32], m: usize) -> Result<(), String> {
+    if m < 2 || m > x.len() / 2 {
+        return Err(format!(
+            "The window must be between 2 and half the series length ({} values)",
+            x.len()
+        ));
+    }
+    Ok(())
+}
+
+fn profile(x: Vec<f32>, m: usize, algorithm: Algorithm) -> (Vec<f32>, Vec<usize>) {
+    match algorithm {
+        Algorithm::Stomp => {
+            let mp = StompMatrixProfile::calculate(x, m);
+            (mp.get_profile().clone(), mp.get_profile_idxs().clone())
+        }
+        Algorithm::Naive => {
+            let mp = NaiveMatrixProfile::calculate(x, m);
+            (mp.get_profile().clone(), mp.get_profile_idxs().clone())
+        }
+    }
+}
+
+fn profile_table(profile: &[f32], profile_idxs: &[usize]) -> Table {
+    let mut table = Table::new(vec!["index", "profile", "profile_idx"]);
+    for (i, (&d, &j)) in profile.iter().zip(profile_idxs).enumerate() {
+        table.push(vec![Value::Index(i), Value::Float(d), Value::Index(j)]);
+    }
+    table
+}
+
+/// Writes the profile as a Parquet table, with the timestamps of the input if it is a table.
+#[cfg(feature = "arrow")]
+fn write_parquet_profile(
+    input: &InputArgs,
+    algorithm: Algorithm,
+    output: &OutputArgs,
+) -> Result<(), Box<dyn Error>> {
+    use matrix_profile::arrow_io::ProfileTable;
+
+    let path = output
+        .output
+        .as_ref()
+        .ok_or("Parquet output must be written to a file")?;
+    let (x, timestamps) = if input::is_table(&input.input) {
+        let res = input::read_table(
+            &input.input,
+            input.column.as_deref(),
+            input.timestamp_column.as_deref(),
+        )?;
+        (res.values, res.timestamps)
+    } else if input.timestamp_column.is_some() {
+        return Err("Timestamps are only read from Arrow IPC and Parquet inputs".into());
+    } else {
+        (read_series(&input.input, input.column.as_deref())?, None)
+    };
+    check_window(&x, input.window)?;
+    let (profile, profile_idxs) = profile(x, input.window, algorithm);
+    let mut table = ProfileTable::new(&profile, &profile_idxs);
+    if let Some(timestamps) = timestamps.as_ref() {
+        table = table.with_timestamps(timestamps);
+    }
+    table.write_parquet(path)?;
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Profile {
+            input,
+            algorithm,
+            output,
+        } => {
+            #[cfg(feature = "arrow")]
+            {
+                if output.format == Format::Parquet {
+                    return write_parquet_profile(&input, algorithm, &output);
+                }
+            }
+            let (profile, profile_idxs) = profile(input.read()?, input.window, algorithm);
+            output.write(&profile_table(&profile, &profile_idxs))
+        }
+        Command::Motifs {
+            input,
+            algorithm,
+            k,
+            output,
+        } => {
+            let m = input.window;
+            let (profile, profile_idxs) = profile(input.read()?, m, algorithm);
+            let mut table = Table::new(vec!["rank", "index", "neighbour", "distance"]);
+            for (rank, motif) in top_motifs(&profile, &profile_idxs, m, k)
+                .into_iter()
+                .enumerate()
+            {
+                table.push(vec![
+                    Value::Index(rank),
+                    Value::Index(motif.idx),
+                    Value::Index(motif.neighbour),
+                    Value::Float(motif.distance),
+                ]);
+            }
+            output.write(&table)
+        }
+        Command::Discords {
+            input,
+            algorithm,
+            k,
+            split,
+            output,
+        } => {
+            let m = input.window;
+            let x = input.read()?;
+            let discords = match algorithm {
+                DiscordAlgorithm::Damp => {
+                    let n = x.len() - m + 1;
+                    let split = split.unwrap_or(n / 4);
+                    let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+                    if split <= exclusion_zone || split >= n {
+                        return Err(format!(
+                            "The split must be between {} and {}",
+                            exclusion_zone + 1,
+                            n - 1
+                        )
+                        .into());
+                    }
+                    let res = DampDiscords::calculate(x, m, split, k);
+                    res.get_discords()
+                        .iter()
+                        .cloned()
+                        .zip(res.get_discord_distances().iter().cloned())
+                        .collect()
+                }
+                DiscordAlgorithm::Stomp | DiscordAlgorithm::Naive => {
+                    let algorithm = if algorithm == DiscordAlgorithm::Stomp {
+                        Algorithm::Stomp
+                    } else {
+                        Algorithm::Naive
+                    };
+                    top_discords(&profile(x, m, algorithm).0, m, k)
+                }
+            };
+            let mut table = Table::new(vec!["rank", "index", "distance"]);
+            for (rank, (i, d)) in discords.into_iter().enumerate() {
+                table.push(vec![Value::Index(rank), Value::Index(i), Value::Float(d)]);
+            }
+            output.write(&table)
+        }
+        Command::Join {
+            input,
+            other,
+            other_column,
+            output,
+        } => {
+            let a = input.read()?;
+            let b = read_series(&other, other_column.as_deref())?;
+            check_window(&b, input.window)?;
+            let res = JoinMatrixProfile::calculate(a, b, input.window);
+            output.write(&profile_table(res.get_profile(), res.get_profile_idxs()))
+        }
+        Command::Segment { input, k, output } => {
+            let m = input.window;
+            let mp = StompMatrixProfile::calculate(input.read()?, m);
+            let cac = corrected_arc_curve(mp.get_profile_idxs(), m);
+            let mut table = Table::new(vec!["rank", "index", "cac"]);
+            for (rank, i) in regime_changes(&cac, m, k).into_iter().enumerate() {
+                table.push(vec![
+                    Value::Index(rank),
+                    Value::Index(i),
+                    Value::Float(cac[i]),
+                ]);
+            }
+            output.write(&table)
+        }
+    }
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_k_at_least_one() {
+        for command in ["motifs", "discords", "segment"].iter() {
+            let args =
+                |k: &'static str| vec!["matrix-profile", command, "x.csv", "-m", "10", "-k", k];
+            assert!(Cli::try_parse_from(args("0")).is_err());
+            assert!(Cli::try_parse_from(args("2")).is_ok());
+        }
+    }
+}
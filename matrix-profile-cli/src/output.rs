@@ -0,0 +1,131 @@
+//! Writes the results as CSV or JSON tables, the profile can also be written as Parquet with the
+//! `arrow` feature.
+use clap::ValueEnum;
+use matrix_profile::NO_NEIGHBOUR;
+use serde_json::json;
+use std::{error::Error, io::Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    /// Array of objects, one per row
+    Json,
+    /// Only for the profile, see `matrix_profile::arrow_io::ProfileTable`
+    #[cfg(feature = "arrow")]
+    Parquet,
+}
+
+/// Cell of a table, missing neighbours and infinite distances are written as empty (CSV) or
+/// `null` (JSON).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Index(usize),
+    Float(f32),
+}
+
+pub struct Table {
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<&'static str>) -> Self {
+        Self {
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    pub fn write<W: Write>(&self, w: W, format: Format) -> Result<(), Box<dyn Error>> {
+        match format {
+            Format::Csv => self.write_csv(w),
+            Format::Json => self.write_json(w),
+            #[cfg(feature = "arrow")]
+            Format::Parquet => Err("Parquet output is only available for the profile".into()),
+        }
+    }
+
+    fn write_csv<W: Write>(&self, w: W) -> Result<(), Box<dyn Error>> {
+        let mut w = csv::Writer::from_writer(w);
+        w.write_record(&self.columns)?;
+        for row in self.rows.iter() {
+            w.write_record(row.iter().map(|o| match *o {
+                Value::Index(i) if i != NO_NEIGHBOUR => i.to_string(),
+                Value::Float(v) if v.is_finite() => v.to_string(),
+                _ => String::new(),
+            }))?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    fn write_json<W: Write>(&self, mut w: W) -> Result<(), Box<dyn Error>> {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                self.columns
+                    .iter()
+                    .zip(row.iter())
+                    .map(|(&name, o)| -> Result<_, Box<dyn Error>> {
+                        let value = match *o {
+                            Value::Index(i) if i != NO_NEIGHBOUR => json!(i),
+                            // Through the shortest decimal representation, so values are not
+                            // written with the noise of the `f64` conversion
+                            Value::Float(v) if v.is_finite() => {
+                                json!(v.to_string().parse::<f64>()?)
+                            }
+                            _ => serde_json::Value::Null,
+                        };
+                        Ok((name.to_string(), value))
+                    })
+                    .collect::<Result<serde_json::Map<_, _>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        serde_json::to_writer(&mut w, &rows)?;
+        writeln!(w)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::new(vec!["index", "profile", "profile_idx"]);
+        table.push(vec![Value::Index(0), Value::Float(0.1), Value::Index(3)]);
+        table.push(vec![
+            Value::Index(1),
+            Value::Float(f32::INFINITY),
+            Value::Index(NO_NEIGHBOUR),
+        ]);
+        table
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut out = Vec::new();
+        table().write(&mut out, Format::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "index,profile,profile_idx\n0,0.1,3\n1,,\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let mut out = Vec::new();
+        table().write(&mut out, Format::Json).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[{\"index\":0,\"profile\":0.1,\"profile_idx\":3},\
+             {\"index\":1,\"profile\":null,\"profile_idx\":null}]\n"
+        );
+    }
+}
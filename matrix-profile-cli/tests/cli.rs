@@ -0,0 +1,95 @@
+//! Runs the subcommands of the binary on the files of `tests/data`: `series.csv` has a date
+//! column, a sine wave turning into a square wave at row 200 and an anomaly at rows 100..110,
+//! `other.txt` is a shifted sine wave as plain numbers.
+use std::process::{Command, Output};
+
+const SERIES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/series.csv");
+const OTHER: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/other.txt");
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_matrix-profile"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs a successful command, returning the header and the rows of its CSV output.
+fn run_csv(args: &[&str]) -> (String, Vec<Vec<String>>) {
+    let output = run(args);
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let header = lines.next().unwrap().to_string();
+    let rows = lines
+        .map(|o| o.split(',').map(String::from).collect())
+        .collect();
+    (header, rows)
+}
+
+fn index(row: &[String], column: usize) -> usize {
+    row[column].parse().unwrap()
+}
+
+#[test]
+fn test_profile() {
+    let (header, rows) = run_csv(&["profile", SERIES, "-m", "20"]);
+    assert_eq!(header, "index,profile,profile_idx");
+    assert_eq!(rows.len(), 381);
+    assert!(rows.iter().all(|o| index(o, 2) < 381));
+
+    let output = run(&["profile", SERIES, "-m", "20", "-a", "naive", "-f", "json"]);
+    assert!(output.status.success());
+    let rows: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(rows.len(), 381);
+    assert_eq!(rows[0]["index"], 0);
+}
+
+#[test]
+fn test_motifs() {
+    let (header, rows) = run_csv(&["motifs", SERIES, "-m", "20", "-k", "2"]);
+    assert_eq!(header, "rank,index,neighbour,distance");
+    assert_eq!(rows.len(), 2);
+    assert!(rows.iter().all(|o| index(o, 1) < index(o, 2)));
+}
+
+#[test]
+fn test_discords() {
+    for algorithm in ["stomp", "naive", "damp"].iter() {
+        let args = ["discords", SERIES, "-m", "20", "-k", "1", "-a", algorithm];
+        let (header, rows) = run_csv(&args);
+        assert_eq!(header, "rank,index,distance");
+        assert_eq!(rows.len(), 1);
+        let i = index(&rows[0], 1);
+        assert!((80..=110).contains(&i), "{}: discord at {}", algorithm, i);
+    }
+}
+
+#[test]
+fn test_join() {
+    let (header, rows) = run_csv(&["join", SERIES, "--other", OTHER, "-m", "20"]);
+    assert_eq!(header, "index,profile,profile_idx");
+    assert_eq!(rows.len(), 381);
+    assert!(rows.iter().all(|o| index(o, 2) < 101));
+}
+
+#[test]
+fn test_segment() {
+    let (header, rows) = run_csv(&["segment", SERIES, "-m", "20", "-k", "1"]);
+    assert_eq!(header, "rank,index,cac");
+    let i = index(&rows[0], 1);
+    assert!((160..=240).contains(&i), "regime change at {}", i);
+}
+
+#[test]
+fn test_invalid_arguments() {
+    let output = run(&["discords", SERIES, "-m", "20", "-a", "damp", "-k", "0"]);
+    assert!(!output.status.success());
+    let output = run(&["profile", SERIES, "-m", "300"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("window"));
+}
@@ -0,0 +1,291 @@
+//! Python bindings of the `matrix_profile` crate.
+//!
+//! `stomp`, the profile helpers and `StreamingMatrixProfile.extend` read contiguous `float32`
+//! NumPy arrays without copying, while `naive`, `topk`, `join` and `damp` copy the series since
+//! their algorithms take ownership of it. Other arrays are converted first, and the GIL is released
+//! while computing. Profiles are returned as `float32`
+//! arrays and indices as `int64` arrays with `-1` for missing neighbours, like the `stumpy`
+//! library.
+use matrix_profile::{
+    motifs, segmentation, DampDiscords, JoinMatrixProfile, MatrixProfile, NaiveMatrixProfile,
+    StompWorkspace, StreamingMatrixProfile, TopKMatrixProfile, NO_NEIGHBOUR,
+};
+use numpy::{AllowTypeChange, IntoPyArray, PyArray1, PyArray2, PyArrayLike1, PyArrayMethods};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use std::borrow::Cow;
+
+type Series<'py> = PyArrayLike1<'py, f32, AllowTypeChange>;
+type ProfileArrays<'py> = (Bound<'py, PyArray1<f32>>, Bound<'py, PyArray1<i64>>);
+type TopKArrays<'py> = (Bound<'py, PyArray2<f32>>, Bound<'py, PyArray2<i64>>);
+type DiscordArrays<'py> = (Bound<'py, PyArray1<i64>>, Bound<'py, PyArray1<f32>>);
+
+/// Values of the array, borrowed when it is contiguous.
+fn values<'a>(x: &'a Series) -> Cow<'a, [f32]> {
+    match x.as_slice() {
+        Ok(o) => Cow::Borrowed(o),
+        Err(_) => Cow::Owned(x.as_array().iter().cloned().collect()),
+    }
+}
+
+fn check_window(len: usize, m: usize) -> PyResult<()> {
+    if m < 2 || m > len {
+        return Err(PyValueError::new_err(format!(
+            "The window must be between 2 and the series length ({})",
+            len
+        )));
+    }
+    Ok(())
+}
+
+fn to_py_idxs(idxs: &[usize]) -> Vec<i64> {
+    idxs.iter()
+        .map(|&o| if o == NO_NEIGHBOUR { -1 } else { o as i64 })
+        .collect()
+}
+
+fn from_py_idxs(idxs: &[i64]) -> Vec<usize> {
+    idxs.iter()
+        .map(|&o| if o < 0 { NO_NEIGHBOUR } else { o as usize })
+        .collect()
+}
+
+fn profile_arrays<'py>(py: Python<'py>, profile: Vec<f32>, idxs: &[usize]) -> ProfileArrays<'py> {
+    (profile.into_pyarray(py), to_py_idxs(idxs).into_pyarray(py))
+}
+
+/// Computes the matrix profile with the STOMP algorithm, returning the profile and its indices.
+#[pyfunction]
+fn stomp<'py>(py: Python<'py>, x: Series<'py>, m: usize) -> PyResult<ProfileArrays<'py>> {
+    let x = values(&x);
+    check_window(x.len(), m)?;
+    let mp = py.detach(|| StompWorkspace::new().calculate(&x, m));
+    Ok(profile_arrays(
+        py,
+        mp.get_profile().clone(),
+        mp.get_profile_idxs(),
+    ))
+}
+
+/// Computes the matrix profile by brute force, only for small series.
+#[pyfunction]
+fn naive<'py>(py: Python<'py>, x: Series<'py>, m: usize) -> PyResult<ProfileArrays<'py>> {
+    let x = values(&x).into_owned();
+    check_window(x.len(), m)?;
+    let mp = py.detach(|| NaiveMatrixProfile::calculate(x, m));
+    Ok(profile_arrays(
+        py,
+        mp.get_profile().clone(),
+        mp.get_profile_idxs(),
+    ))
+}
+
+/// Computes the `k` nearest neighbours of each subsequence, as `(k, n)` arrays.
+#[pyfunction]
+fn topk<'py>(py: Python<'py>, x: Series<'py>, m: usize, k: usize) -> PyResult<TopKArrays<'py>> {
+    let x = values(&x).into_owned();
+    check_window(x.len(), m)?;
+    if k == 0 {
+        return Err(PyValueError::new_err("k must be at least 1"));
+    }
+    let mp = py.detach(|| TopKMatrixProfile::calculate(x, m, k));
+    let shape = mp.get_profiles().dim();
+    let profiles = mp.get_profiles().iter().cloned().collect::<Vec<_>>();
+    let idxs = mp.get_profiles_idxs().iter().cloned().collect::<Vec<_>>();
+    Ok((
+        PyArray1::from_vec(py, profiles).reshape([shape.0, shape.1])?,
+        PyArray1::from_vec(py, to_py_idxs(&idxs)).reshape([shape.0, shape.1])?,
+    ))
+}
+
+/// Computes the nearest neighbour of each subsequence of `a` in `b` (AB-join).
+#[pyfunction]
+fn join<'py>(
+    py: Python<'py>,
+    a: Series<'py>,
+    b: Series<'py>,
+    m: usize,
+) -> PyResult<ProfileArrays<'py>> {
+    let (a, b) = (values(&a).into_owned(), values(&b).into_owned());
+    check_window(a.len().min(b.len()), m)?;
+    let mp = py.detach(|| JoinMatrixProfile::calculate(a, b, m));
+    Ok(profile_arrays(
+        py,
+        mp.get_profile().clone(),
+        mp.get_profile_idxs(),
+    ))
+}
+
+/// Finds the top `k` discords after `split` with the DAMP algorithm, returning their indices and
+/// scores.
+#[pyfunction]
+fn damp<'py>(
+    py: Python<'py>,
+    x: Series<'py>,
+    m: usize,
+    split: usize,
+    k: usize,
+) -> PyResult<DiscordArrays<'py>> {
+    let x = values(&x).into_owned();
+    check_window(x.len(), m)?;
+    let n = x.len() - m + 1;
+    let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+    if split <= exclusion_zone || split >= n || k == 0 {
+        return Err(PyValueError::new_err(format!(
+            "The split must be between {} and {}, and k at least 1",
+            exclusion_zone + 1,
+            n - 1
+        )));
+    }
+    let res = py.detach(|| DampDiscords::calculate(x, m, split, k));
+    Ok((
+        to_py_idxs(res.get_discords()).into_pyarray(py),
+        res.get_discord_distances().clone().into_pyarray(py),
+    ))
+}
+
+/// Top `k` motifs of a profile as `(index, neighbour, distance)` tuples.
+#[pyfunction]
+fn top_motifs(
+    profile: Series,
+    profile_idxs: PyArrayLike1<i64, AllowTypeChange>,
+    m: usize,
+    k: usize,
+) -> Vec<(usize, usize, f32)> {
+    let idxs = from_py_idxs(&profile_idxs.as_array().iter().cloned().collect::<Vec<_>>());
+    motifs::top_motifs(&values(&profile), &idxs, m, k)
+        .into_iter()
+        .map(|o| (o.idx, o.neighbour, o.distance))
+        .collect()
+}
+
+/// Top `k` discords of a profile as `(index, distance)` tuples.
+#[pyfunction]
+fn top_discords(profile: Series, m: usize, k: usize) -> Vec<(usize, f32)> {
+    motifs::top_discords(&values(&profile), m, k)
+}
+
+/// Corrected arc curve of the FLUSS segmentation algorithm.
+#[pyfunction]
+fn corrected_arc_curve<'py>(
+    py: Python<'py>,
+    profile_idxs: PyArrayLike1<'py, i64, AllowTypeChange>,
+    m: usize,
+) -> Bound<'py, PyArray1<f32>> {
+    let idxs = from_py_idxs(&profile_idxs.as_array().iter().cloned().collect::<Vec<_>>());
+    segmentation::corrected_arc_curve(&idxs, m).into_pyarray(py)
+}
+
+/// Top `k` regime changes of a corrected arc curve.
+#[pyfunction]
+fn regime_changes(cac: Series, m: usize, k: usize) -> Vec<usize> {
+    segmentation::regime_changes(&values(&cac), m, k)
+}
+
+/// Matrix profile updated incrementally as values arrive.
+#[pyclass(name = "StreamingMatrixProfile")]
+struct PyStreamingMatrixProfile {
+    inner: StreamingMatrixProfile,
+}
+
+#[pymethods]
+impl PyStreamingMatrixProfile {
+    #[new]
+    fn new(m: usize) -> PyResult<Self> {
+        check_window(m, m)?;
+        Ok(Self {
+            inner: StreamingMatrixProfile::new(m),
+        })
+    }
+
+    /// Adds a value, NaN for missing values.
+    fn push(&mut self, value: f32) {
+        self.inner.push(value)
+    }
+
+    /// Adds the values of an array in order.
+    fn extend(&mut self, py: Python<'_>, x: Series<'_>) {
+        let x = values(&x);
+        let inner = &mut self.inner;
+        py.detach(|| inner.extend(x.iter().cloned()));
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    #[getter]
+    fn window_size(&self) -> usize {
+        self.inner.get_window_size()
+    }
+
+    #[getter]
+    fn profile<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f32>> {
+        PyArray1::from_slice(py, self.inner.get_profile())
+    }
+
+    #[getter]
+    fn profile_idxs<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<i64>> {
+        to_py_idxs(self.inner.get_profile_idxs()).into_pyarray(py)
+    }
+
+    #[getter]
+    fn left_profile_idxs<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<i64>> {
+        to_py_idxs(self.inner.get_left_profile_idxs()).into_pyarray(py)
+    }
+
+    #[getter]
+    fn right_profile_idxs<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<i64>> {
+        to_py_idxs(self.inner.get_right_profile_idxs()).into_pyarray(py)
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "matrix_profile")]
+fn py_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(stomp, m)?)?;
+    m.add_function(wrap_pyfunction!(naive, m)?)?;
+    m.add_function(wrap_pyfunction!(topk, m)?)?;
+    m.add_function(wrap_pyfunction!(join, m)?)?;
+    m.add_function(wrap_pyfunction!(damp, m)?)?;
+    m.add_function(wrap_pyfunction!(top_motifs, m)?)?;
+    m.add_function(wrap_pyfunction!(top_discords, m)?)?;
+    m.add_function(wrap_pyfunction!(corrected_arc_curve, m)?)?;
+    m.add_function(wrap_pyfunction!(regime_changes, m)?)?;
+    m.add_class::<PyStreamingMatrixProfile>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_helpers() {
+        assert!(check_window(10, 2).is_ok());
+        assert!(check_window(10, 10).is_ok());
+        assert!(check_window(10, 1).is_err());
+        assert!(check_window(10, 11).is_err());
+
+        let idxs = vec![3, NO_NEIGHBOUR, 0];
+        assert_eq!(to_py_idxs(&idxs), vec![3, -1, 0]);
+        assert_eq!(from_py_idxs(&to_py_idxs(&idxs)), idxs);
+    }
+
+    /// Runs `tests/test_matrix_profile.py` with the module embedded in the interpreter, with
+    /// `cargo test -p matrix-profile-py -- --ignored`.
+    #[test]
+    #[ignore = "needs the numpy and pytest Python packages"]
+    fn test_python() {
+        pyo3::append_to_inittab!(py_module);
+        Python::initialize();
+        Python::attach(|py| {
+            let args = vec![concat!(env!("CARGO_MANIFEST_DIR"), "/tests"), "-q"];
+            let code: i32 = py
+                .import("pytest")
+                .and_then(|o| o.call_method1("main", (args,)))
+                .and_then(|o| o.extract())
+                .unwrap();
+            assert_eq!(code, 0);
+        });
+    }
+}
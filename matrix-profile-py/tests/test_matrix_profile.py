@@ -0,0 +1,92 @@
+import threading
+
+import numpy as np
+import pytest
+
+import matrix_profile as mp
+
+
+def naive_profile(x, m):
+    """Brute force z-normalized matrix profile."""
+    n = len(x) - m + 1
+    subs = np.array([x[i : i + m] for i in range(n)], dtype=np.float64)
+    subs = (subs - subs.mean(axis=1, keepdims=True)) / subs.std(axis=1, keepdims=True)
+    d = np.sqrt(np.maximum(2 * m * (1 - subs @ subs.T / m), 0))
+    ez = int(np.ceil(m / 4))
+    for i in range(n):
+        d[i, max(0, i - ez) : i + ez + 1] = np.inf
+    return d.min(axis=1), d.argmin(axis=1)
+
+
+@pytest.fixture
+def series():
+    return np.random.default_rng(34).standard_normal(300).astype(np.float32)
+
+
+def test_stomp_and_naive(series):
+    expected, expected_idxs = naive_profile(series, 20)
+    for algorithm in (mp.stomp, mp.naive):
+        profile, idxs = algorithm(series, 20)
+        assert profile.dtype == np.float32 and idxs.dtype == np.int64
+        np.testing.assert_allclose(profile, expected, atol=1e-3)
+        np.testing.assert_array_equal(idxs, expected_idxs)
+    # Other types and non contiguous arrays are converted
+    profile, _ = mp.stomp(series.astype(np.float64)[::1], 20)
+    np.testing.assert_allclose(profile, expected, atol=1e-3)
+    with pytest.raises(ValueError):
+        mp.stomp(series, 1)
+
+
+def test_missing_values(series):
+    x = series.copy()
+    x[100] = np.nan
+    profile, idxs = mp.stomp(x, 20)
+    assert np.isinf(profile[90]) and idxs[90] == -1
+
+
+def test_helpers(series):
+    profile, idxs = mp.stomp(series, 20)
+    motifs = mp.top_motifs(profile, idxs, 20, 2)
+    assert len(motifs) == 2 and motifs[0][2] == pytest.approx(profile.min(), abs=1e-5)
+    discords = mp.top_discords(profile, 20, 1)
+    assert discords[0][0] == int(np.argmax(profile))
+    cac = mp.corrected_arc_curve(idxs, 20)
+    assert len(cac) == len(profile)
+    assert len(mp.regime_changes(cac, 20, 1)) == 1
+
+    profiles, profiles_idxs = mp.topk(series, 20, 3)
+    assert profiles.shape == (3, len(profile))
+    np.testing.assert_allclose(profiles[0], profile, atol=1e-3)
+
+    join, join_idxs = mp.join(series[:150], series[150:], 20)
+    assert join.shape == (131,) and join_idxs.max() < 131
+
+    discords, scores = mp.damp(series, 20, 100, 2)
+    assert discords.dtype == np.int64 and len(scores) == 2
+
+
+def test_streaming(series):
+    stream = mp.StreamingMatrixProfile(20)
+    stream.extend(series[:200])
+    for value in series[200:]:
+        stream.push(value)
+    assert len(stream) == 300 and stream.window_size == 20
+    expected, expected_idxs = naive_profile(series, 20)
+    np.testing.assert_allclose(stream.profile, expected, atol=1e-3)
+    np.testing.assert_array_equal(stream.profile_idxs, expected_idxs)
+    assert stream.left_profile_idxs[0] == -1 and stream.right_profile_idxs[-1] == -1
+
+
+def test_threads(series):
+    results = [None] * 4
+
+    def run(i):
+        results[i] = mp.stomp(series, 20)[0]
+
+    threads = [threading.Thread(target=run, args=(i,)) for i in range(4)]
+    for t in threads:
+        t.start()
+    for t in threads:
+        t.join()
+    for res in results:
+        np.testing.assert_array_equal(res, results[0])
@@ -1,4 +1,6 @@
-use matrix_profile::{naive, stomp, MatrixProfile};
+use matrix_profile::{
+    naive, stomp, CancellationToken, MatrixProfile, Outcome, ProgressObserver, NO_NEIGHBOUR,
+};
 use wasm_bindgen::prelude::*;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -7,9 +9,58 @@ use wasm_bindgen::prelude::*;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+/// Reports the progress to a JavaScript callback `(rowsDone, totalRows) => boolean` about every
+/// percent of the rows, the computation is cancelled when the callback returns `false`.
+struct JsObserver<'a> {
+    callback: &'a js_sys::Function,
+    token: CancellationToken,
+}
+
+impl<'a> ProgressObserver for JsObserver<'a> {
+    fn on_progress(&mut self, rows_done: usize, total_rows: usize) {
+        let step = (total_rows / 100).max(1);
+        if !rows_done.is_multiple_of(step) && rows_done != total_rows {
+            return;
+        }
+        let res = self.callback.call2(
+            &JsValue::NULL,
+            &JsValue::from(rows_done as u32),
+            &JsValue::from(total_rows as u32),
+        );
+        if res.ok().and_then(|o| o.as_bool()) == Some(false) {
+            self.token.cancel();
+        }
+    }
+}
+
+/// Runs a computation with a [`JsObserver`], returning the (possibly partial) result and
+/// whether it was cancelled.
+fn with_js_progress<T>(
+    callback: &js_sys::Function,
+    calculate: impl FnOnce(&mut dyn ProgressObserver, &CancellationToken) -> Outcome<T>,
+) -> (T, bool) {
+    let token = CancellationToken::new();
+    let mut observer = JsObserver {
+        callback,
+        token: token.clone(),
+    };
+    let res = calculate(&mut observer, &token);
+    let cancelled = res.is_cancelled();
+    (res.into_inner(), cancelled)
+}
+
+/// Indices for JavaScript, where [`NO_NEIGHBOUR`] would be `4294967295` on wasm32, so missing
+/// neighbours are `-1` instead.
+fn to_js_idxs(idxs: &[usize]) -> Vec<i32> {
+    idxs.iter()
+        .map(|&o| if o == NO_NEIGHBOUR { -1 } else { o as i32 })
+        .collect()
+}
+
 #[wasm_bindgen]
 pub struct NaiveMatrixProfile {
     data: naive::NaiveMatrixProfile,
+    cancelled: bool,
 }
 
 #[wasm_bindgen]
@@ -22,7 +73,26 @@ impl NaiveMatrixProfile {
     /// * `m` - Window size
     pub fn calculate(x: Vec<f32>, m: usize) -> Self {
         let data = naive::NaiveMatrixProfile::calculate(x, m);
-        Self { data }
+        Self {
+            data,
+            cancelled: false,
+        }
+    }
+
+    /// Computes the matrix profile using a naive (brute force) algorithm, reporting the progress
+    /// to `callback`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `callback` - Called with `(rowsDone, totalRows)`, returning `false` cancels the
+    ///   computation
+    pub fn calculate_with_progress(x: Vec<f32>, m: usize, callback: &js_sys::Function) -> Self {
+        let (data, cancelled) = with_js_progress(callback, |observer, token| {
+            naive::NaiveMatrixProfile::calculate_with_progress(x, m, observer, token)
+        });
+        Self { data, cancelled }
     }
 
     /// Computes the full matrix profile using a naive (brute force) algorithm.
@@ -33,21 +103,31 @@ impl NaiveMatrixProfile {
     /// * `m` - Window size
     pub fn calculate_full_matrix(x: Vec<f32>, m: usize) -> Self {
         let data = naive::NaiveMatrixProfile::calculate_full_matrix(x, m);
-        Self { data }
+        Self {
+            data,
+            cancelled: false,
+        }
     }
 
     pub fn get_profile(&self) -> Vec<f32> {
         self.data.get_profile().clone()
     }
 
-    pub fn get_profile_idxs(&self) -> Vec<usize> {
-        self.data.get_profile_idxs().clone()
+    /// Index of the nearest neighbour of each subsequence, -1 without neighbour.
+    pub fn get_profile_idxs(&self) -> Vec<i32> {
+        to_js_idxs(self.data.get_profile_idxs())
+    }
+
+    /// Whether the computation was cancelled, the profile is then partial.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
     }
 }
 
 #[wasm_bindgen]
 pub struct StompMatrixProfile {
     data: stomp::StompMatrixProfile,
+    cancelled: bool,
 }
 
 #[wasm_bindgen]
@@ -62,15 +142,42 @@ impl StompMatrixProfile {
     /// [Stomp]: https://www.cs.ucr.edu/~eamonn/MatrixProfile.html
     pub fn calculate(x: Vec<f32>, m: usize) -> Self {
         let data = stomp::StompMatrixProfile::calculate(x, m);
-        Self { data }
+        Self {
+            data,
+            cancelled: false,
+        }
+    }
+
+    /// Computes the matrix profile using the [Stomp] algorithm, reporting the progress to
+    /// `callback`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `callback` - Called with `(rowsDone, totalRows)`, returning `false` cancels the
+    ///   computation
+    ///
+    /// [Stomp]: https://www.cs.ucr.edu/~eamonn/MatrixProfile.html
+    pub fn calculate_with_progress(x: Vec<f32>, m: usize, callback: &js_sys::Function) -> Self {
+        let (data, cancelled) = with_js_progress(callback, |observer, token| {
+            stomp::StompMatrixProfile::calculate_with_progress(x, m, observer, token)
+        });
+        Self { data, cancelled }
     }
 
     pub fn get_profile(&self) -> Vec<f32> {
         self.data.get_profile().clone()
     }
 
-    pub fn get_profile_idxs(&self) -> Vec<usize> {
-        self.data.get_profile_idxs().clone()
+    /// Index of the nearest neighbour of each subsequence, -1 without neighbour.
+    pub fn get_profile_idxs(&self) -> Vec<i32> {
+        to_js_idxs(self.data.get_profile_idxs())
+    }
+
+    /// Whether the computation was cancelled, the profile is then partial.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
     }
 }
 
@@ -85,6 +192,16 @@ mod tests {
         println!("{:?}", res.get_profile());
     }
 
+    #[test]
+    fn test_missing_neighbours() {
+        let mut x: Vec<f32> = (0..20).map(|i| (i as f32).sin()).collect();
+        x[3] = f32::NAN;
+        let res = StompMatrixProfile::calculate(x, 4);
+        let idxs = res.get_profile_idxs();
+        assert_eq!(&idxs[..4], &[-1; 4]);
+        assert!(idxs[4..].iter().all(|&o| (4..17).contains(&o)));
+    }
+
     #[test]
     fn test_stomp() {
         let x: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0, 1.0, 4.0, 5.0, 12.0, 4.0, 5.0];
@@ -4,7 +4,7 @@ import { wrap } from "comlink";
 // eslint-disable-next-line import/no-webpack-loader-syntax
 import Worker from "worker-loader!./wasm.worker.ts";
 import { TWorker } from "./wasm.worker";
-import { clamp, extractFilename } from "./utils";
+import { clamp, extractFilename, finiteOrNaN } from "./utils";
 
 const worker = wrap<TWorker>(new Worker());
 
@@ -151,7 +151,9 @@ export const useStore = create<TStore>((set, get) => ({
   getNearestNeighbour: (idx) => {
     const pIdxs = get().profileIdxs;
     if (pIdxs === undefined) return -1;
-    return pIdxs[Math.round(idx)] ?? -1;
+    // Subsequences touching missing values have no neighbour (-1)
+    const nn = pIdxs[Math.round(idx)] ?? -1;
+    return nn >= 0 ? nn : -1;
   },
   brushPosition: -1,
   nearestNeighbourPosition: -1,
@@ -189,7 +191,8 @@ export const useStore = create<TStore>((set, get) => ({
   calculateDiscord: () => {
     set(({ profile }) => {
       if (profile === undefined) return {};
-      const idx = maxIndex(profile);
+      // Subsequences touching missing values have infinite distances, d3 skips NaN
+      const idx = maxIndex(profile, finiteOrNaN);
       if (idx === -1) return {};
       const discord = [idx, profile[idx]];
       return { discord };
@@ -203,7 +206,7 @@ export const useStore = create<TStore>((set, get) => ({
   calculateMotif: () => {
     set(({ profile }) => {
       if (profile === undefined) return {};
-      const idx = minIndex(profile);
+      const idx = minIndex(profile, finiteOrNaN);
       if (idx === -1) return {};
       const motif = [idx, profile[idx]];
       return { motif };
@@ -2,6 +2,8 @@ export const clamp = (x: number, min: number, max: number) => {
   return x < min ? min : x > max ? max : x;
 };
 
+export const finiteOrNaN = (x: number) => (Number.isFinite(x) ? x : NaN);
+
 export const sleep = (delay: number) =>
   new Promise((resolve) => setTimeout(resolve, delay));
 
@@ -4,10 +4,17 @@ import wasm from "matrix-profile-wasm";
 export type TWorker = {
   module: typeof wasm | null;
   loadModule: () => void;
+  /**
+   * Computes the matrix profile. `onProgress` (wrapped with comlink's `proxy`)
+   * receives the rows done and total rows, and setting `cancelFlag[0]` to 1
+   * (a shared `Int32Array`) stops the computation with a partial result.
+   */
   calculate: (
     x: Float32Array,
-    m: number
-  ) => Promise<{ profile: number[]; profileIdxs: number[] }>;
+    m: number,
+    onProgress?: (rowsDone: number, totalRows: number) => void,
+    cancelFlag?: Int32Array
+  ) => Promise<{ profile: number[]; profileIdxs: number[]; cancelled: boolean }>;
 };
 
 const exports: TWorker = {
@@ -15,13 +22,21 @@ const exports: TWorker = {
   async loadModule() {
     if (this.module === null) this.module = await import("matrix-profile-wasm");
   },
-  async calculate(x, m) {
+  async calculate(x, m, onProgress, cancelFlag) {
     await this.loadModule();
     if (this.module === null) throw new Error("Failed to load wasm module");
-    const res = await this.module.StompMatrixProfile.calculate(x, m);
+    const res = await this.module.StompMatrixProfile.calculate_with_progress(
+      x,
+      m,
+      (rowsDone: number, totalRows: number) => {
+        if (onProgress) onProgress(rowsDone, totalRows);
+        return !(cancelFlag && Atomics.load(cancelFlag, 0) === 1);
+      }
+    );
     const profile = Array.from(res.get_profile());
     const profileIdxs = Array.from(res.get_profile_idxs());
-    return { profile, profileIdxs };
+    const cancelled = res.is_cancelled();
+    return { profile, profileIdxs, cancelled };
   },
 };
 
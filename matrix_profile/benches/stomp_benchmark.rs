@@ -1,13 +1,65 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use matrix_profile::{MatrixProfile, NaiveMatrixProfile, StompMatrixProfile};
-use rand::{distributions::Uniform, Rng, SeedableRng};
-use rand_chacha::ChaCha8Rng;
-
-pub fn random_data(n: usize, seed: u64) -> Vec<f32> {
-    let rng = ChaCha8Rng::seed_from_u64(seed);
-    rng.sample_iter(Uniform::new_inclusive(0.0, 100.0))
-        .take(n)
-        .collect::<Vec<_>>()
+use matrix_profile::{
+    datasets::random_walk,
+    mass::sliding_dot_product,
+    simd::Kernel,
+    stats::{precompute_stats, remove_mean},
+    utils::{fill_missing, valid_subsequences},
+    DampDiscords, MatrixProfile, NaiveMatrixProfile, StompMatrixProfile, StompWorkspace,
+};
+use ndarray::prelude::*;
+
+/// STOMP as implemented before [`StompWorkspace`], cloning the `qt` row and allocating the
+/// distance profile on every row. Kept as the baseline of the workspace benchmark.
+fn stomp_baseline(x: Vec<f32>, m: usize) -> Vec<f32> {
+    let x = Array1::from(x);
+    let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+    let valid = valid_subsequences(&x, m);
+    let x = fill_missing(&remove_mean(&x));
+    let (mean_t, sigma_t) = precompute_stats(&x, m);
+    let mut qt = sliding_dot_product(x.slice(s![..m]), x.view());
+    let qt_first = qt.clone();
+    let n = qt.len();
+    let mut profile = vec![f32::INFINITY; n];
+
+    for idx in 0..n {
+        if idx > 0 {
+            let qt_ = qt.clone().into_iter();
+            qt.iter_mut()
+                .skip(1)
+                .zip(qt_)
+                .enumerate()
+                .for_each(|(i, (a, b))| *a = b - (x[i] * x[idx - 1]) + (x[i + m] * x[idx + m - 1]));
+            qt[0] = qt_first[idx];
+        }
+        if !valid[idx] {
+            continue;
+        }
+        let q_std = sigma_t[idx].max(f32::EPSILON);
+        let mut distances = qt
+            .iter()
+            .zip(mean_t.iter())
+            .zip(sigma_t.iter())
+            .map(|((t, mt), st)| {
+                2.0 * ((m as f32) - (t - (m as f32) * mt * mean_t[idx]) / (q_std * st))
+            })
+            .map(|o| if o < f32::EPSILON { 0f32 } else { o })
+            .zip(valid.iter())
+            .map(|(o, &v)| if v { o } else { f32::INFINITY })
+            .collect::<Vec<_>>();
+        let min_idx = idx.saturating_sub(exclusion_zone);
+        let max_idx = (idx + exclusion_zone).min(n - 1);
+        distances[min_idx..=max_idx]
+            .iter_mut()
+            .for_each(|o| *o = f32::INFINITY);
+        for (profile_d, &d) in profile.iter_mut().zip(distances.iter()) {
+            if *profile_d > d {
+                *profile_d = d;
+            }
+        }
+    }
+    profile.iter_mut().for_each(|o| *o = o.sqrt());
+    profile
 }
 
 pub fn criterion_benchmark(c: &mut Criterion) {
@@ -16,14 +68,76 @@ pub fn criterion_benchmark(c: &mut Criterion) {
 
     for n in [500, 1000, 2000].iter() {
         group.bench_with_input(BenchmarkId::new("Naive", n), n, |b, &n| {
-            b.iter(|| NaiveMatrixProfile::calculate(random_data(n, 34), 100))
+            b.iter(|| NaiveMatrixProfile::calculate(random_walk(n, 34), 100))
         });
         group.bench_with_input(BenchmarkId::new("Stump", n), n, |b, &n| {
-            b.iter(|| StompMatrixProfile::calculate(random_data(n, 34), 100))
+            b.iter(|| StompMatrixProfile::calculate(random_walk(n, 34), 100))
         });
+        group.bench_with_input(BenchmarkId::new("Damp", n), n, |b, &n| {
+            b.iter(|| DampDiscords::calculate(random_walk(n, 34), 100, n / 4, 1))
+        });
+    }
+    group.finish();
+}
+
+pub fn workspace_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Repeated profiles");
+    group.sample_size(10);
+    let windows = [50, 100, 200];
+
+    for n in [1000, 2000, 4000].iter() {
+        let x = random_walk(*n, 34);
+        group.bench_with_input(BenchmarkId::new("Stomp baseline", n), &x, |b, x| {
+            b.iter(|| {
+                for &m in windows.iter() {
+                    stomp_baseline(x.clone(), m);
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("Stomp", n), &x, |b, x| {
+            b.iter(|| {
+                for &m in windows.iter() {
+                    StompMatrixProfile::calculate(x.clone(), m);
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("Stomp workspace", n), &x, |b, x| {
+            let mut workspace = StompWorkspace::new();
+            b.iter(|| {
+                for &m in windows.iter() {
+                    workspace.calculate(x, m);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Compares the vectorised kernels, only the scalar one is available without the `simd` feature.
+pub fn kernel_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Kernels");
+    group.sample_size(10);
+
+    for n in [2000, 8000].iter() {
+        let x = random_walk(*n, 34);
+        for kernel in [Kernel::Scalar, Kernel::Avx2, Kernel::Avx512].iter() {
+            if !kernel.is_available() {
+                continue;
+            }
+            let name = format!("{:?}", kernel);
+            group.bench_with_input(BenchmarkId::new(name, n), &x, |b, x| {
+                let mut workspace = StompWorkspace::with_kernel(*kernel);
+                b.iter(|| workspace.calculate(x, 100))
+            });
+        }
     }
     group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    workspace_benchmark,
+    kernel_benchmark
+);
 criterion_main!(benches);
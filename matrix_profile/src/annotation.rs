@@ -0,0 +1,257 @@
+//! Annotation vectors used to guide the motif search, check [here] for more details.
+//!
+//! An annotation vector has one value in `[0, 1]` per subsequence, where `0` marks a subsequence
+//! as uninteresting and `1` as interesting. The corrected matrix profile pushes the uninteresting
+//! subsequences away from the motifs.
+//!
+//! [here]: https://www.cs.ucr.edu/~eamonn/guided-motif-KDD17-new-format-10-pages-v005.pdf
+use crate::utils::{euclidean_distance, normalize, valid_subsequences};
+use ndarray::prelude::*;
+
+/// Built-in and user supplied annotation vectors.
+pub enum AnnotationVector {
+    /// Favours complex subsequences, using the complexity estimate (length of the line
+    /// obtained by "stretching" the subsequence).
+    Complexity,
+    /// Suppresses the subsequences whose z-normalized distance to `word` is below `threshold`.
+    StopWord { word: Vec<f32>, threshold: f32 },
+    /// Suppresses flat regions: subsequences whose standard deviation is below `threshold`.
+    FlatRegion { threshold: f32 },
+    /// Suppresses subsequences with many values clipped at the global minimum or maximum.
+    Clipping,
+    /// User supplied annotation vector, must have one value in `[0, 1]` per subsequence.
+    Custom(Vec<f32>),
+}
+
+impl AnnotationVector {
+    /// Computes the annotation vector for the time series, of length `n - m + 1`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    pub fn compute(&self, x: &[f32], m: usize) -> Vec<f32> {
+        let x = Array1::from(x.to_vec());
+        let valid = valid_subsequences(&x, m);
+        let subsequences = valid.iter().enumerate().map(|(i, &v)| {
+            if v {
+                Some(x.slice(s![i..(i + m)]))
+            } else {
+                None
+            }
+        });
+
+        let av = match self {
+            Self::Complexity => {
+                let ce = subsequences
+                    .map(|o| {
+                        o.map_or(0.0, |o| {
+                            o.windows(2)
+                                .into_iter()
+                                .map(|w| (w[1] - w[0]).powi(2))
+                                .sum::<f32>()
+                                .sqrt()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                min_max_scale(ce, &valid)
+            }
+            Self::StopWord { word, threshold } => {
+                assert_eq!(
+                    word.len(),
+                    m,
+                    "The stop word must have the window size length"
+                );
+                let word = normalize(Array1::from(word.clone()));
+                subsequences
+                    .map(|o| {
+                        o.map_or(0.0, |o| {
+                            let distance = euclidean_distance(&word, &normalize(o.to_owned()));
+                            if distance < *threshold {
+                                0.0
+                            } else {
+                                1.0
+                            }
+                        })
+                    })
+                    .collect()
+            }
+            Self::FlatRegion { threshold } => subsequences
+                .map(|o| {
+                    o.map_or(0.0, |o| {
+                        if o.std_axis(Axis(0), 0.0).into_scalar() < *threshold {
+                            0.0
+                        } else {
+                            1.0
+                        }
+                    })
+                })
+                .collect(),
+            Self::Clipping => {
+                let finite = x.iter().filter(|o| o.is_finite());
+                let min = finite.clone().fold(f32::INFINITY, |a, &b| a.min(b));
+                let max = finite.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+                let clipped = subsequences
+                    .map(|o| {
+                        o.map_or(0.0, |o| {
+                            o.iter().filter(|&&v| (v == min) || (v == max)).count() as f32
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                min_max_scale(clipped, &valid)
+                    .into_iter()
+                    .map(|o| 1.0 - o)
+                    .collect()
+            }
+            Self::Custom(av) => {
+                assert_eq!(
+                    av.len(),
+                    valid.len(),
+                    "The annotation vector must have one value per subsequence"
+                );
+                av.clone()
+            }
+        };
+
+        // Subsequences touching missing values are never interesting
+        av.into_iter()
+            .zip(valid)
+            .map(|(o, v)| if v { o } else { 0.0 })
+            .collect()
+    }
+}
+
+/// Scales the values to the `[0, 1]` range using the range of the valid ones only, a constant
+/// vector is mapped to ones. The invalid values are left for the caller to replace.
+fn min_max_scale(x: Vec<f32>, valid: &[bool]) -> Vec<f32> {
+    let valid_values = x.iter().zip(valid).filter(|(_, &v)| v).map(|(o, _)| o);
+    let min = valid_values.clone().fold(f32::INFINITY, |a, &b| a.min(b));
+    let max = valid_values.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+    let range = max - min;
+    if range <= 0.0 {
+        return vec![1.0; x.len()];
+    }
+    x.into_iter().map(|o| (o - min) / range).collect()
+}
+
+/// Computes the corrected matrix profile: `profile + (1 - av) * max(profile)`, so subsequences
+/// annotated with `0` can't be chosen as motifs over any annotated with `1`.
+///
+/// # Arguments
+///
+/// * `profile` - Matrix profile
+/// * `av` - Annotation vector, with values in `[0, 1]` and the same length as the profile
+pub fn corrected_profile(profile: &[f32], av: &[f32]) -> Vec<f32> {
+    assert_eq!(
+        profile.len(),
+        av.len(),
+        "The annotation vector must have the same length as the matrix profile"
+    );
+    assert!(
+        av.iter().all(|o| (0.0..=1.0).contains(o)),
+        "The annotation vector values must be in [0, 1]"
+    );
+    let max = profile
+        .iter()
+        .filter(|o| o.is_finite())
+        .fold(0f32, |a, &b| a.max(b));
+    profile
+        .iter()
+        .zip(av)
+        .map(|(p, a)| p + (1.0 - a) * max)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix_profile::MatrixProfile, stomp::StompMatrixProfile, test_utils::random_data,
+    };
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_complexity() {
+        let x = vec![0.0, 0.0, 0.0, 0.0, 1.0, -1.0, 1.0, -1.0];
+        let av = AnnotationVector::Complexity.compute(&x, 4);
+        assert_eq!(av.len(), 5);
+        assert_relative_eq!(av[0], 0.0);
+        assert_relative_eq!(av[4], 1.0);
+        assert!(av.windows(2).all(|o| o[0] <= o[1]));
+    }
+
+    #[test]
+    fn test_flat_region_and_clipping() {
+        let x = vec![5.0, 5.0, 5.0, 5.0, 1.0, 3.0, 2.0, 4.0, 10.0, 10.0];
+        let av = AnnotationVector::FlatRegion { threshold: 0.1 }.compute(&x, 3);
+        assert_eq!(av, vec![0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        let av = AnnotationVector::Clipping.compute(&x, 3);
+        assert_relative_eq!(
+            av.as_slice(),
+            [1.0, 1.0, 0.5, 0.5, 0.5, 1.0, 0.5, 0.0].as_ref()
+        );
+    }
+
+    #[test]
+    fn test_stop_word() {
+        let x = random_data(50, 34);
+        let word = x[10..15].to_vec();
+        let av = AnnotationVector::StopWord {
+            word,
+            threshold: 0.1,
+        }
+        .compute(&x, 5);
+        assert_eq!(av.len(), 46);
+        assert_relative_eq!(av[10], 0.0);
+        assert_eq!(av.iter().filter(|&&o| o == 0.0).count(), 1);
+    }
+
+    #[test]
+    fn test_corrected_profile() {
+        let mut x = random_data(100, 34);
+        x[50] = f32::NAN;
+        let m = 10;
+        let mp = StompMatrixProfile::calculate(x.clone(), m);
+        let profile = mp.get_profile();
+        let max = profile
+            .iter()
+            .filter(|o| o.is_finite())
+            .fold(0f32, |a, &b| a.max(b));
+
+        let av = AnnotationVector::Complexity.compute(&x, m);
+        let cmp = corrected_profile(profile, &av);
+        for ((c, p), a) in cmp.iter().zip(profile).zip(&av) {
+            if p.is_finite() {
+                assert!(c >= p);
+                assert_relative_eq!(*c, p + (1.0 - a) * max);
+            } else {
+                assert!(c.is_infinite());
+            }
+        }
+
+        let av = AnnotationVector::Custom(vec![1.0; profile.len()]).compute(&x, m);
+        assert_eq!(&corrected_profile(profile, &av), profile);
+    }
+
+    #[test]
+    fn test_missing_values() {
+        // The missing subsequences don't take part in the scaling
+        let x = vec![0.0, 1.0, 0.0, 1.0, 0.0, 2.0, 0.0, 2.0, f32::NAN];
+        let av = AnnotationVector::Complexity.compute(&x, 4);
+        let expected = AnnotationVector::Complexity.compute(&x[..8], 4);
+        assert_relative_eq!(av[0], 0.0);
+        assert_relative_eq!(&av[..5], expected.as_slice());
+        assert_eq!(&av[5..], [0.0; 1].as_ref());
+
+        let x = vec![5.0, 1.0, 3.0, 2.0, 1.0, f32::NAN, 2.0, 5.0];
+        let av = AnnotationVector::Clipping.compute(&x, 3);
+        assert_relative_eq!(av.as_slice(), [0.0, 1.0, 1.0, 0.0, 0.0, 0.0].as_ref());
+    }
+
+    #[test]
+    #[should_panic(expected = "one value per subsequence")]
+    fn test_custom_length() {
+        let x = random_data(100, 34);
+        AnnotationVector::Custom(vec![1.0; 100]).compute(&x, 10);
+    }
+}
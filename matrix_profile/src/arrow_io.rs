@@ -0,0 +1,393 @@
+//! Reading series from Apache Arrow IPC and Parquet files, and writing the results as Parquet
+//! tables. Enabled with the `arrow` feature.
+//!
+//! Null values are read as missing values (`NaN`), and infinite distances and missing neighbours
+//! ([`NO_NEIGHBOUR`]) are written as nulls.
+use crate::matrix_profile::NO_NEIGHBOUR;
+use arrow::{
+    array::{Array, ArrayRef, Float32Array, TimestampNanosecondArray, UInt64Array},
+    compute::cast,
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    error::ArrowError,
+    ipc::reader::{FileReader, StreamReader},
+    record_batch::{RecordBatch, RecordBatchReader},
+};
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom},
+    path::Path,
+    sync::Arc,
+};
+
+/// Series read from a table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnSeries {
+    /// Values of the numeric column, nulls are `NaN`.
+    pub values: Vec<f32>,
+    /// Values of the timestamp column, if requested.
+    pub timestamps: Option<TimestampNanosecondArray>,
+}
+
+/// Reads a series from an Arrow IPC file, in either the file or the stream format.
+///
+/// # Arguments
+///
+/// * `path` - Arrow IPC file
+/// * `column` - Name of the numeric column, any type that casts to `f32`
+/// * `timestamp_column` - Name of a column with the timestamps, any type that casts to a
+///   timestamp (integers are taken as nanoseconds since the epoch)
+pub fn read_ipc<P: AsRef<Path>>(
+    path: P,
+    column: &str,
+    timestamp_column: Option<&str>,
+) -> io::Result<ColumnSeries> {
+    let mut file = File::open(path)?;
+    match FileReader::try_new_buffered(&mut file, None) {
+        Ok(reader) => read_batches(reader.schema(), reader, column, timestamp_column),
+        Err(_) => {
+            file.seek(SeekFrom::Start(0))?;
+            let reader = StreamReader::try_new_buffered(file, None).map_err(invalid_data)?;
+            read_batches(reader.schema(), reader, column, timestamp_column)
+        }
+    }
+}
+
+/// Reads a series from a Parquet file, see [`read_ipc`] for the arguments.
+pub fn read_parquet<P: AsRef<Path>>(
+    path: P,
+    column: &str,
+    timestamp_column: Option<&str>,
+) -> io::Result<ColumnSeries> {
+    let builder =
+        ParquetRecordBatchReaderBuilder::try_new(File::open(path)?).map_err(invalid_data)?;
+    let reader = builder.build().map_err(invalid_data)?;
+    read_batches(reader.schema(), reader, column, timestamp_column)
+}
+
+fn read_batches<I: Iterator<Item = Result<RecordBatch, ArrowError>>>(
+    schema: SchemaRef,
+    batches: I,
+    column: &str,
+    timestamp_column: Option<&str>,
+) -> io::Result<ColumnSeries> {
+    let find = |name: &str| {
+        schema.index_of(name).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Column '{}' not found", name),
+            )
+        })
+    };
+    let value_idx = find(column)?;
+    let timestamp_idx = timestamp_column.map(find).transpose()?;
+
+    let mut values = Vec::new();
+    let mut timestamps = Vec::new();
+    for batch in batches {
+        let batch = batch.map_err(invalid_data)?;
+        let array = cast(batch.column(value_idx), &DataType::Float32).map_err(invalid_data)?;
+        let array = array.as_any().downcast_ref::<Float32Array>().unwrap();
+        values.extend(array.iter().map(|o| o.unwrap_or(f32::NAN)));
+        if let Some(idx) = timestamp_idx {
+            let column = batch.column(idx);
+            let timezone = match column.data_type() {
+                DataType::Timestamp(_, timezone) => timezone.clone(),
+                _ => None,
+            };
+            timestamps.push(
+                cast(column, &DataType::Timestamp(TimeUnit::Nanosecond, timezone))
+                    .map_err(invalid_data)?,
+            );
+        }
+    }
+
+    let timestamps = match timestamp_idx {
+        Some(_) if timestamps.is_empty() => Some(TimestampNanosecondArray::from(Vec::<i64>::new())),
+        Some(_) => {
+            let arrays = timestamps.iter().map(|o| o.as_ref()).collect::<Vec<_>>();
+            let array = arrow::compute::concat(&arrays).map_err(invalid_data)?;
+            Some(
+                array
+                    .as_any()
+                    .downcast_ref::<TimestampNanosecondArray>()
+                    .unwrap()
+                    .clone(),
+            )
+        }
+        None => None,
+    };
+    Ok(ColumnSeries { values, timestamps })
+}
+
+/// Table with a matrix profile, written as one row per subsequence with the columns `index`,
+/// `timestamp` (if given), `profile`, `profile_idx`, `left_idx` and `right_idx` (if given).
+pub struct ProfileTable<'a> {
+    profile: &'a [f32],
+    profile_idxs: &'a [usize],
+    timestamps: Option<&'a TimestampNanosecondArray>,
+    left_idxs: Option<&'a [usize]>,
+    right_idxs: Option<&'a [usize]>,
+}
+
+impl<'a> ProfileTable<'a> {
+    pub fn new(profile: &'a [f32], profile_idxs: &'a [usize]) -> Self {
+        assert_eq!(
+            profile.len(),
+            profile_idxs.len(),
+            "The profile and its indices must have the same length"
+        );
+        Self {
+            profile,
+            profile_idxs,
+            timestamps: None,
+            left_idxs: None,
+            right_idxs: None,
+        }
+    }
+
+    /// Timestamps of the series, each subsequence gets the timestamp of its first value.
+    pub fn with_timestamps(mut self, timestamps: &'a TimestampNanosecondArray) -> Self {
+        assert!(
+            timestamps.len() >= self.profile.len(),
+            "There must be a timestamp for each subsequence"
+        );
+        self.timestamps = Some(timestamps);
+        self
+    }
+
+    /// Indices of the left matrix profile (nearest neighbour before each subsequence).
+    pub fn with_left_idxs(mut self, left_idxs: &'a [usize]) -> Self {
+        assert_eq!(left_idxs.len(), self.profile.len());
+        self.left_idxs = Some(left_idxs);
+        self
+    }
+
+    /// Indices of the right matrix profile (nearest neighbour after each subsequence).
+    pub fn with_right_idxs(mut self, right_idxs: &'a [usize]) -> Self {
+        assert_eq!(right_idxs.len(), self.profile.len());
+        self.right_idxs = Some(right_idxs);
+        self
+    }
+
+    pub fn to_record_batch(&self) -> RecordBatch {
+        let n = self.profile.len();
+        let mut fields = vec![Field::new("index", DataType::UInt64, false)];
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(UInt64Array::from_iter_values(0..n as u64))];
+        if let Some(timestamps) = self.timestamps {
+            let timestamps = timestamps.slice(0, n);
+            fields.push(Field::new(
+                "timestamp",
+                timestamps.data_type().clone(),
+                true,
+            ));
+            columns.push(Arc::new(timestamps));
+        }
+        fields.push(Field::new("profile", DataType::Float32, true));
+        columns.push(Arc::new(
+            self.profile
+                .iter()
+                .map(|&o| if o.is_finite() { Some(o) } else { None })
+                .collect::<Float32Array>(),
+        ));
+        let idxs = [
+            ("profile_idx", Some(self.profile_idxs)),
+            ("left_idx", self.left_idxs),
+            ("right_idx", self.right_idxs),
+        ];
+        for (name, idxs) in idxs.iter() {
+            if let Some(idxs) = idxs {
+                fields.push(Field::new(*name, DataType::UInt64, true));
+                columns.push(Arc::new(
+                    idxs.iter()
+                        .map(|&o| {
+                            if o == NO_NEIGHBOUR {
+                                None
+                            } else {
+                                Some(o as u64)
+                            }
+                        })
+                        .collect::<UInt64Array>(),
+                ));
+            }
+        }
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    }
+
+    /// Writes the table as a Snappy compressed Parquet file.
+    pub fn write_parquet<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let batch = self.to_record_batch();
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(props))
+            .map_err(invalid_data)?;
+        writer.write(&batch).map_err(invalid_data)?;
+        writer.close().map_err(invalid_data)?;
+        Ok(())
+    }
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::random_data, MatrixProfile, StompMatrixProfile};
+    use arrow::{
+        array::{Float64Array, Int32Array},
+        ipc::writer::{FileWriter, StreamWriter},
+    };
+    use std::env::temp_dir;
+
+    fn input_batch(x: &[f32]) -> RecordBatch {
+        let values = x
+            .iter()
+            .map(|&o| if o.is_nan() { None } else { Some(o as f64) })
+            .collect::<Float64Array>();
+        let timestamps = (0..x.len() as i64)
+            .map(|i| 1_600_000_000_000 + i * 1000)
+            .collect::<Vec<_>>();
+        let timestamps =
+            arrow::array::TimestampMillisecondArray::from(timestamps).with_timezone("UTC");
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("value", DataType::Float64, true),
+            Field::new("time", timestamps.data_type().clone(), false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..x.len() as i32)),
+                Arc::new(values),
+                Arc::new(timestamps),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_read_ipc_and_parquet() {
+        let mut x = random_data(60, 34);
+        x[10] = f32::NAN;
+        let batch = input_batch(&x);
+        let (first, second) = (batch.slice(0, 25), batch.slice(25, 35));
+        let dir = temp_dir();
+
+        let file_path = dir.join("matrix_profile_test_file.arrow");
+        let mut writer =
+            FileWriter::try_new(File::create(&file_path).unwrap(), &batch.schema()).unwrap();
+        writer.write(&first).unwrap();
+        writer.write(&second).unwrap();
+        writer.finish().unwrap();
+
+        let stream_path = dir.join("matrix_profile_test_stream.arrows");
+        let mut writer =
+            StreamWriter::try_new(File::create(&stream_path).unwrap(), &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let parquet_path = dir.join("matrix_profile_test_input.parquet");
+        let mut writer =
+            ArrowWriter::try_new(File::create(&parquet_path).unwrap(), batch.schema(), None)
+                .unwrap();
+        writer.write(&first).unwrap();
+        writer.write(&second).unwrap();
+        writer.close().unwrap();
+
+        for res in [
+            read_ipc(&file_path, "value", Some("time")).unwrap(),
+            read_ipc(&stream_path, "value", Some("time")).unwrap(),
+            read_parquet(&parquet_path, "value", Some("time")).unwrap(),
+        ]
+        .iter()
+        {
+            assert_eq!(res.values.len(), 60);
+            assert!(res.values[10].is_nan());
+            assert_eq!(res.values[..10], x[..10]);
+            assert_eq!(res.values[11..], x[11..]);
+            let timestamps = res.timestamps.as_ref().unwrap();
+            assert_eq!(timestamps.timezone(), Some("UTC"));
+            assert_eq!(timestamps.value(3), 1_600_000_003_000_000_000);
+        }
+
+        let res = read_parquet(&parquet_path, "id", None).unwrap();
+        assert_eq!(res.values[59], 59.0);
+        assert!(res.timestamps.is_none());
+        let err = read_ipc(&file_path, "missing", None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        for path in [file_path, stream_path, parquet_path].iter() {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_write_parquet() {
+        let mut x = random_data(60, 34);
+        x[10] = f32::NAN;
+        let m = 8;
+        let mp = StompMatrixProfile::calculate(x.clone(), m);
+        let series = ColumnSeries {
+            values: x,
+            timestamps: Some(TimestampNanosecondArray::from(
+                (0..60).map(|i| i * 10).collect::<Vec<i64>>(),
+            )),
+        };
+        let right_idxs = vec![NO_NEIGHBOUR; mp.get_profile().len()];
+        let path = temp_dir().join("matrix_profile_test_output.parquet");
+        ProfileTable::new(mp.get_profile(), mp.get_profile_idxs())
+            .with_timestamps(series.timestamps.as_ref().unwrap())
+            .with_right_idxs(&right_idxs)
+            .write_parquet(&path)
+            .unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let batch = &batches[0];
+        let names = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|o| o.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["index", "timestamp", "profile", "profile_idx", "right_idx"]
+        );
+        assert_eq!(batch.num_rows(), 53);
+
+        let column = |name: &str| batch.column(batch.schema().index_of(name).unwrap()).clone();
+        let timestamps = column("timestamp");
+        let timestamps = timestamps
+            .as_any()
+            .downcast_ref::<TimestampNanosecondArray>()
+            .unwrap();
+        assert_eq!(timestamps.value(52), 520);
+        let profile = column("profile");
+        let profile = profile.as_any().downcast_ref::<Float32Array>().unwrap();
+        let idxs = column("profile_idx");
+        let idxs = idxs.as_any().downcast_ref::<UInt64Array>().unwrap();
+        for i in 0..53 {
+            let d = mp.get_profile()[i];
+            assert_eq!(profile.is_null(i), !d.is_finite());
+            if d.is_finite() {
+                assert_eq!(profile.value(i), d);
+                assert_eq!(idxs.value(i) as usize, mp.get_profile_idxs()[i]);
+            } else {
+                assert!(idxs.is_null(i));
+            }
+        }
+        assert_eq!(column("right_idx").null_count(), 53);
+    }
+}
@@ -0,0 +1,48 @@
+//! Worker process for [`matrix_profile::distributed::Coordinator`], answers the tile jobs
+//! received on stdin through stdout.
+//!
+//! With `--socket <path>` it listens on a Unix socket instead, serving each connection (see
+//! `Coordinator::connect`) until it is killed.
+use matrix_profile::distributed::serve_worker;
+use std::{env, io};
+
+fn main() -> io::Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.as_slice() {
+        [] => {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            serve_worker(stdin.lock(), stdout.lock())
+        }
+        [flag, path] if flag == "--socket" => listen(path),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Usage: tile_worker [--socket <path>]",
+        )),
+    }
+}
+
+#[cfg(unix)]
+fn listen(path: &str) -> io::Result<()> {
+    let listener = std::os::unix::net::UnixListener::bind(path)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            let res = stream
+                .try_clone()
+                .and_then(|input| serve_worker(input, stream));
+            if let Err(e) = res {
+                eprintln!("tile_worker: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_path: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix sockets are not available on this platform",
+    ))
+}
@@ -0,0 +1,479 @@
+//! Property based cross-validation of every algorithm against the brute force reference,
+//! [`NaiveMatrixProfile::calculate_full_matrix`], on random series with random windows, offsets,
+//! scales, constant runs and missing values.
+//!
+//! # Tolerance
+//!
+//! The algorithms work in single precision with different operation orders (sliding dot
+//! products, rolling statistics, FFTs), so the squared distances they compute must match the
+//! reference ones within [`TOLERANCE`] times `2m`, the squared distance of uncorrelated
+//! subsequences. Comparing the distances themselves would fail close to zero, where the square
+//! root amplifies the rounding errors. Series with subsequences whose standard deviation is below
+//! [`CONDITIONING`] times the largest deviation from the mean of the series are not generated
+//! (constant ones aside), single precision can't resolve their distances.
+//!
+//! # Tie-breaking
+//!
+//! Equal distances resolve to the lowest index (see [`MatrixProfile`]), but the distances
+//! computed by each algorithm differ by rounding. So neighbours whose reference distances are
+//! within the tolerance of each other are ties here, any of them is accepted: the index returned
+//! must be a neighbour whose reference distance is within the tolerance of the nearest one.
+//! Subsequences without neighbour (touching missing values, or with all the candidates in the
+//! "exclusion zone") must have an infinite distance and [`NO_NEIGHBOUR`] as index.
+//!
+//! New algorithms must be added to [`check_all`].
+use crate::{
+    join::JoinMatrixProfile,
+    matrix_profile::{MatrixProfile, NO_NEIGHBOUR},
+    naive::NaiveMatrixProfile,
+    simd::Kernel,
+    stomp::{DriftControl, StompMatrixProfile, StompWorkspace},
+    streaming::StreamingMatrixProfile,
+    tiles::calculate_tiled,
+    topk::TopKMatrixProfile,
+    utils::{constant_subsequences, valid_subsequences},
+    DampDiscords, DistanceMatrix,
+};
+use ndarray::prelude::*;
+use proptest::prelude::*;
+
+/// Maximum difference between a squared distance and the reference one, relative to `2m`.
+const TOLERANCE: f32 = 1e-3;
+
+/// Smallest standard deviation of a subsequence relative to the magnitude of the series, see
+/// [`well_conditioned`].
+const CONDITIONING: f64 = 0.02;
+
+/// Whether a distance matches the reference one within the tolerance.
+fn close(d: f32, expected: f32, m: usize) -> bool {
+    d == expected || (d * d - expected * expected).abs() <= TOLERANCE * 2.0 * m as f32
+}
+
+#[derive(Clone, Debug)]
+struct Case {
+    x: Vec<f32>,
+    /// Second series for the AB-join.
+    y: Vec<f32>,
+    m: usize,
+    /// Start of the DAMP search, as a fraction of the subsequences.
+    split: f32,
+    tile_size: usize,
+}
+
+/// Shape of the generated series.
+#[derive(Clone, Copy, Debug)]
+enum Shape {
+    Noise,
+    RandomWalk,
+    Sine,
+}
+
+fn series(n: usize, m: usize) -> impl Strategy<Value = Vec<f32>> {
+    let shape = prop_oneof![
+        Just(Shape::Noise),
+        Just(Shape::RandomWalk),
+        Just(Shape::Sine)
+    ];
+    let scale = prop_oneof![Just(0.1f32), Just(1.0), Just(10.0), Just(100.0)];
+    let runs = prop::collection::vec((0..n, 1..=(2 * m)), 0..3);
+    let missing = prop::collection::vec(
+        (
+            0..n,
+            prop_oneof![Just(f32::NAN), Just(f32::INFINITY), Just(f32::NEG_INFINITY)],
+        ),
+        0..3,
+    );
+    (
+        prop::collection::vec(-1f32..1f32, n),
+        shape,
+        scale,
+        -100f32..100f32,
+        runs,
+        missing,
+    )
+        .prop_map(|(noise, shape, scale, offset, runs, missing)| {
+            let mut walk = 0f32;
+            let mut x = noise
+                .iter()
+                .enumerate()
+                .map(|(i, &o)| {
+                    let v = match shape {
+                        Shape::Noise => o,
+                        Shape::RandomWalk => {
+                            walk += o;
+                            walk
+                        }
+                        Shape::Sine => (i as f32 * 0.3).sin() + o * 0.1,
+                    };
+                    (v + offset) * scale
+                })
+                .collect::<Vec<_>>();
+            // Constant runs repeat the value at their start
+            for (start, len) in runs {
+                let end = (start + len).min(x.len());
+                let v = x[start];
+                x[start..end].iter_mut().for_each(|o| *o = v);
+            }
+            for (i, v) in missing {
+                x[i] = v;
+            }
+            x
+        })
+        .prop_filter("ill-conditioned subsequences", move |x| {
+            well_conditioned(x, m)
+        })
+}
+
+/// Checks the valid non-constant subsequences have a standard deviation of at least [`CONDITIONING`]
+/// times the largest deviation from the mean of the series. The algorithms center the series and
+/// derive the correlations from dot products accumulated along it, whose rounding errors grow
+/// with the square of the ratio between both.
+fn well_conditioned(x: &[f32], m: usize) -> bool {
+    let x_array = Array1::from(x.to_vec());
+    let valid = valid_subsequences(&x_array, m);
+    let constant = constant_subsequences(&x_array, m);
+    let finite = x.iter().filter(|o| o.is_finite()).map(|&o| o as f64);
+    let center = finite.clone().sum::<f64>() / finite.clone().count().max(1) as f64;
+    let magnitude = finite.map(|o| (o - center).abs()).fold(0.0, f64::max);
+    x.windows(m).enumerate().all(|(i, w)| {
+        let mean = w.iter().map(|&o| o as f64).sum::<f64>() / m as f64;
+        let std = (w.iter().map(|&o| (o as f64 - mean).powi(2)).sum::<f64>() / m as f64).sqrt();
+        !valid[i] || constant[i] || std >= CONDITIONING * magnitude
+    })
+}
+
+fn case() -> impl Strategy<Value = Case> {
+    (3usize..=16)
+        .prop_flat_map(|m| (Just(m), (m + 4)..=(m + 100), (m + 4)..=(m + 40)))
+        .prop_flat_map(|(m, n, n_y)| {
+            (
+                series(n, m),
+                series(n_y, m),
+                Just(m),
+                0f32..1f32,
+                1..=(n - m + 1),
+            )
+        })
+        .prop_map(|(x, y, m, split, tile_size)| Case {
+            x,
+            y,
+            m,
+            split,
+            tile_size,
+        })
+}
+
+/// Object safe access to the results of [`MatrixProfile`] implementations.
+trait MatrixProfileView {
+    fn profile(&self) -> &[f32];
+    fn profile_idxs(&self) -> &[usize];
+    fn window_size(&self) -> usize;
+    fn exclusion_zone(&self) -> usize;
+}
+
+impl<T: MatrixProfile> MatrixProfileView for T {
+    fn profile(&self) -> &[f32] {
+        self.get_profile()
+    }
+
+    fn profile_idxs(&self) -> &[usize] {
+        self.get_profile_idxs()
+    }
+
+    fn window_size(&self) -> usize {
+        self.get_window_size()
+    }
+
+    fn exclusion_zone(&self) -> usize {
+        self.get_exclusion_zone()
+    }
+}
+
+/// Nearest reference neighbour among the candidates, `(INFINITY, NO_NEIGHBOUR)` if none.
+fn nearest(row: ArrayView1<f32>, candidates: std::ops::Range<usize>) -> (f32, usize) {
+    candidates
+        .filter(|&j| row[j].is_finite())
+        .fold((f32::INFINITY, NO_NEIGHBOUR), |best, j| {
+            if row[j] < best.0 {
+                (row[j], j)
+            } else {
+                best
+            }
+        })
+}
+
+/// Checks a neighbour `(d, j)` of row `i` against the nearest reference one, `j` being a column
+/// of the reference matrix.
+fn check_neighbour(
+    name: &str,
+    i: usize,
+    (d, j): (f32, usize),
+    (expected_d, expected_j): (f32, usize),
+    row: ArrayView1<f32>,
+    m: usize,
+) {
+    if expected_j == NO_NEIGHBOUR {
+        assert!(
+            d.is_infinite() && j == NO_NEIGHBOUR,
+            "{}: row {} has no neighbour, got ({}, {})",
+            name,
+            i,
+            d,
+            j
+        );
+        return;
+    }
+    assert!(
+        close(d, expected_d, m),
+        "{}: row {} distance {} != {}",
+        name,
+        i,
+        d,
+        expected_d
+    );
+    assert!(
+        j != NO_NEIGHBOUR && close(row[j], expected_d, m),
+        "{}: row {} neighbour {} is not tied with {}",
+        name,
+        i,
+        j,
+        expected_j
+    );
+}
+
+fn check_profile(name: &str, profile: &[f32], idxs: &[usize], matrix: &Array2<f32>, m: usize) {
+    let n = matrix.nrows();
+    assert_eq!(profile.len(), n, "{}: wrong profile length", name);
+    assert_eq!(idxs.len(), n, "{}: wrong indices length", name);
+    for (i, row) in matrix.outer_iter().enumerate() {
+        let expected = nearest(row, 0..n);
+        check_neighbour(name, i, (profile[i], idxs[i]), expected, row, m);
+    }
+}
+
+fn check_all(case: &Case) {
+    let Case { x, y, m, .. } = case;
+    let m = *m;
+    let reference = NaiveMatrixProfile::calculate_full_matrix(x.clone(), m);
+    let matrix = reference.get_full_matrix().unwrap();
+    let n = matrix.nrows();
+
+    let exclusion_zone = reference.get_exclusion_zone();
+    let check = |name: &str, mp: &dyn MatrixProfileView| {
+        check_profile(name, mp.profile(), mp.profile_idxs(), matrix, m);
+        assert_eq!(mp.window_size(), m, "{}: wrong window", name);
+        assert_eq!(
+            mp.exclusion_zone(),
+            exclusion_zone,
+            "{}: wrong exclusion zone",
+            name
+        );
+    };
+    check("naive", &NaiveMatrixProfile::calculate(x.clone(), m));
+    check("stomp", &StompMatrixProfile::calculate(x.clone(), m));
+    for &kernel in [Kernel::Scalar, Kernel::Avx2, Kernel::Avx512].iter() {
+        if kernel.is_available() {
+            let mp = StompWorkspace::with_kernel(kernel).calculate(x, m);
+            check(&format!("stomp {:?}", kernel), &mp);
+        }
+    }
+    let drift_control = DriftControl {
+        refresh_every: Some(7),
+        compensated: true,
+        measure_every: None,
+    };
+    check(
+        "stomp with drift control",
+        &StompMatrixProfile::calculate_with_drift_control(x.clone(), m, drift_control),
+    );
+
+    // Streaming, also with the neighbours before and after each subsequence
+    let mp = StreamingMatrixProfile::calculate(x.clone(), m);
+    check("streaming", &mp);
+    for (i, row) in matrix.outer_iter().enumerate() {
+        let left = (mp.get_left_profile()[i], mp.get_left_profile_idxs()[i]);
+        check_neighbour("streaming left", i, left, nearest(row, 0..i), row, m);
+        let right = (mp.get_right_profile()[i], mp.get_right_profile_idxs()[i]);
+        check_neighbour("streaming right", i, right, nearest(row, i..n), row, m);
+    }
+
+    // Tiles, sequentially and by distributed workers
+    let mp = calculate_tiled(x, m, case.tile_size);
+    check_profile("tiles", mp.get_profile(), mp.get_profile_idxs(), matrix, m);
+    #[cfg(unix)]
+    {
+        let mp = distributed(x, m, case.tile_size);
+        check_profile(
+            "distributed",
+            mp.get_profile(),
+            mp.get_profile_idxs(),
+            matrix,
+            m,
+        );
+    }
+
+    // Each of the k nearest neighbours, in ascending order
+    let k = 3;
+    let mp = TopKMatrixProfile::calculate(x.clone(), m, k);
+    for (i, row) in matrix.outer_iter().enumerate() {
+        let mut sorted = row
+            .iter()
+            .cloned()
+            .filter(|o| o.is_finite())
+            .collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let idxs = mp.get_profiles_idxs().row(i);
+        for r in 0..k {
+            let expected = match sorted.get(r) {
+                Some(&d) => (d, row.iter().position(|&o| o == d).unwrap()),
+                None => (f32::INFINITY, NO_NEIGHBOUR),
+            };
+            let found = (mp.get_profiles()[[i, r]], idxs[r]);
+            check_neighbour(&format!("top-{} {}", k, r), i, found, expected, row, m);
+        }
+        let mut neighbours = idxs
+            .iter()
+            .filter(|&&o| o != NO_NEIGHBOUR)
+            .collect::<Vec<_>>();
+        let len = neighbours.len();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        assert_eq!(
+            neighbours.len(),
+            len,
+            "top-{}: row {} repeats neighbours",
+            k,
+            i
+        );
+    }
+
+    // Rows of the lazy distance matrix
+    let distances = DistanceMatrix::new(x, m);
+    for (i, row) in matrix.outer_iter().enumerate() {
+        for (j, (&d, &expected)) in distances.row(i).iter().zip(row.iter()).enumerate() {
+            assert!(
+                close(d, expected, m),
+                "distance matrix: cell ({}, {}) {} != {}",
+                i,
+                j,
+                d,
+                expected
+            );
+        }
+    }
+
+    // AB-join, the reference is the self-join of both series separated by a missing value,
+    // which keeps every pair of subsequences out of the "exclusion zone"
+    let offset = x.len() + 1;
+    let joined = x
+        .iter()
+        .cloned()
+        .chain(std::iter::once(f32::NAN))
+        .chain(y.iter().cloned())
+        .collect::<Vec<_>>();
+    let reference = NaiveMatrixProfile::calculate_full_matrix(joined, m);
+    let joined_matrix = reference.get_full_matrix().unwrap();
+    let mp = JoinMatrixProfile::calculate(x.clone(), y.clone(), m);
+    assert_eq!(mp.get_profile().len(), n, "join: wrong profile length");
+    for i in 0..n {
+        let row = joined_matrix.row(i);
+        let expected = nearest(row, offset..row.len());
+        let j = mp.get_profile_idxs()[i];
+        let found = (
+            mp.get_profile()[i],
+            if j == NO_NEIGHBOUR { j } else { j + offset },
+        );
+        check_neighbour("join", i, found, expected, row, m);
+    }
+
+    // DAMP, the top discord is the subsequence after the split with the farthest neighbour
+    // before it
+    if n > exclusion_zone + 2 {
+        let split = exclusion_zone + 1 + (case.split * (n - exclusion_zone - 2) as f32) as usize;
+        let left = |i: usize| nearest(matrix.row(i), 0..i).0;
+        let scores = (split..n)
+            .map(left)
+            .filter(|o| o.is_finite())
+            .collect::<Vec<_>>();
+        let res = DampDiscords::calculate(x.clone(), m, split, 1);
+        match scores.iter().cloned().reduce(f32::max) {
+            None => assert!(res.get_discords().is_empty(), "damp: unexpected discord"),
+            Some(top) => {
+                assert_eq!(res.get_discords().len(), 1, "damp: discord not found");
+                let (i, d) = (res.get_discords()[0], res.get_discord_distances()[0]);
+                assert!(
+                    i >= split && close(d, top, m) && close(left(i), d, m),
+                    "damp: discord ({}, {}) but the top score is {}",
+                    i,
+                    d,
+                    top
+                );
+            }
+        }
+    }
+}
+
+/// Runs the tiles on two workers served by threads through Unix sockets.
+#[cfg(unix)]
+fn distributed(x: &[f32], m: usize, tile_size: usize) -> crate::tiles::PartialProfile {
+    use crate::distributed::{serve_worker, Coordinator};
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    static CASES: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "mp_cross_validation_{}_{}.f32",
+        std::process::id(),
+        CASES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let bytes = x.iter().flat_map(|o| o.to_ne_bytes()).collect::<Vec<_>>();
+    std::fs::write(&path, bytes).unwrap();
+
+    let mut workers = Vec::new();
+    let mut connections: Vec<(Box<dyn Read + Send>, Box<dyn Write + Send>)> = Vec::new();
+    for _ in 0..2 {
+        let (coordinator_side, worker_side) = UnixStream::pair().unwrap();
+        workers.push(thread::spawn(move || {
+            serve_worker(worker_side.try_clone().unwrap(), worker_side)
+        }));
+        connections.push((
+            Box::new(coordinator_side.try_clone().unwrap()),
+            Box::new(coordinator_side),
+        ));
+    }
+    let res = Coordinator::new(connections).run(&path, m, tile_size);
+    for worker in workers {
+        worker.join().unwrap().unwrap();
+    }
+    std::fs::remove_file(&path).unwrap();
+    res.unwrap()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_cross_validation(case in case()) {
+        check_all(&case);
+    }
+}
+
+#[test]
+fn test_constant_and_missing() {
+    // Constant subsequences next to subsequences without neighbour
+    let mut x = crate::test_utils::random_data(80, 34);
+    x[10..30].iter_mut().for_each(|o| *o = 5.0);
+    x[50] = f32::NAN;
+    x[60] = f32::NEG_INFINITY;
+    check_all(&Case {
+        y: x[20..].to_vec(),
+        x,
+        m: 8,
+        split: 0.5,
+        tile_size: 16,
+    });
+}
@@ -0,0 +1,261 @@
+//! Implementation of the DAMP algorithm to find the top discords of a time series without
+//! computing the full Matrix Profile, check [here] for more details.
+//!
+//! A discord score is the left matrix profile value: the distance of a subsequence to its nearest
+//! neighbour in the past. The search is processed backwards in chunks of increasing size and
+//! stops as soon as a neighbour closer than the best discord so far is found, while the forward
+//! pass prunes the upcoming subsequences that are close to the current one.
+//!
+//! [here]: https://www.cs.ucr.edu/~eamonn/DAMP_long_version.pdf
+use crate::{
+    mass::MassPlanner,
+    stats::remove_mean,
+    utils::{constant_squared_distance, constant_subsequences, fill_missing, valid_subsequences},
+};
+use ndarray::prelude::*;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DampDiscords {
+    /// Discord indices, sorted by decreasing score.
+    discords: Vec<usize>,
+    /// Discord scores (left matrix profile values).
+    distances: Vec<f32>,
+    /// Window size.
+    m: usize,
+    /// Nearby subsequences excluded as neighbours, at most this far apart.
+    exclusion_zone: usize,
+}
+
+impl DampDiscords {
+    /// Finds the top discords after the `split` point using the DAMP algorithm.
+    ///
+    /// The result is exact: every returned score is the true left matrix profile value and no
+    /// subsequence with a larger score is missed. Discords are not allowed to be closer than `m`
+    /// to each other, keeping the highest scoring one (the lowest index on ties).
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `split` - Index where the search for discords starts, the data before is only used as
+    ///   reference
+    /// * `k` - Number of discords to find
+    pub fn calculate(x: Vec<f32>, m: usize, split: usize, k: usize) -> Self {
+        assert!(k > 0, "At least one discord must be searched");
+        let x = Array1::from(x);
+        let n = x.len() - m + 1;
+        // Nearby subsequences are likely highly similar so we define an "exclusion zone" around the diagonal
+        let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+        assert!(
+            split > exclusion_zone && split < n,
+            "The split point must leave at least one subsequence on each side"
+        );
+        let valid = valid_subsequences(&x, m);
+        let constant = constant_subsequences(&x, m);
+        let x = fill_missing(&remove_mean(&x));
+
+        let initial_chunk = (8 * m).next_power_of_two();
+        let lookahead = (16 * m).next_power_of_two();
+        let mut pruned = vec![false; n];
+        // The chunk sizes repeat between subsequences, so the FFT plans are reused
+        let mut planner = MassPlanner::new();
+        let mut discords: Vec<(usize, f32)> = Vec::with_capacity(k + 1);
+
+        for i in split..n {
+            if pruned[i] || !valid[i] {
+                continue;
+            }
+            let query = x.slice(s![i..(i + m)]);
+            // Best discord score that can still make it to the top discords
+            let bsf = if discords.len() < k {
+                f32::NEG_INFINITY
+            } else {
+                discords[k - 1].1
+            };
+
+            // Backward processing, neighbours are in [0, last]
+            let last = i - exclusion_zone - 1;
+            let mut chunk = initial_chunk;
+            let mut end = last;
+            let mut distance = f32::INFINITY;
+            loop {
+                let start = (last + 1).saturating_sub(chunk);
+                distance = distance.min(min_distance(
+                    &mut planner,
+                    (query, constant[i]),
+                    x.slice(s![start..(end + m)]),
+                    &valid[start..=end],
+                    &constant[start..=end],
+                    m,
+                ));
+                if start == 0 {
+                    // Reached the beginning, the score is exact
+                    if distance.is_finite() && distance > bsf {
+                        insert_discord(&mut discords, (i, distance), k, m);
+                    }
+                    break;
+                }
+                if distance < bsf {
+                    break;
+                }
+                end = start - 1;
+                chunk *= 2;
+            }
+
+            // Forward processing, prune the subsequences that have `i` as a close left neighbour
+            let bsf = if discords.len() < k {
+                f32::NEG_INFINITY
+            } else {
+                discords[k - 1].1
+            };
+            let start = i + exclusion_zone + 1;
+            if start < n {
+                let end = (start + lookahead - 1).min(n - 1);
+                let distances = planner.mass(query, x.slice(s![start..(end + m)]));
+                for (j, d) in distances.into_iter().enumerate() {
+                    let d = constant_squared_distance(constant[i], constant[start + j], m)
+                        .map_or(d, f32::sqrt);
+                    if d < bsf {
+                        pruned[start + j] = true;
+                    }
+                }
+            }
+        }
+
+        let (discords, distances) = discords.into_iter().unzip();
+        Self {
+            discords,
+            distances,
+            m,
+            exclusion_zone,
+        }
+    }
+
+    /// Discord indices, sorted by decreasing score.
+    pub fn get_discords(&self) -> &Vec<usize> {
+        &self.discords
+    }
+
+    /// Discord scores, the distance of each discord to its nearest neighbour in the past.
+    pub fn get_discord_distances(&self) -> &Vec<f32> {
+        &self.distances
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    pub fn get_exclusion_zone(&self) -> usize {
+        self.exclusion_zone
+    }
+}
+
+/// Minimum distance between the query (and whether it is constant) and the valid subsequences
+/// of `t`.
+fn min_distance(
+    planner: &mut MassPlanner,
+    (query, query_constant): (ArrayView1<f32>, bool),
+    t: ArrayView1<f32>,
+    valid: &[bool],
+    constant: &[bool],
+    m: usize,
+) -> f32 {
+    planner
+        .mass(query, t)
+        .into_iter()
+        .zip(valid.iter().zip(constant))
+        .filter(|(_, (&v, _))| v)
+        .map(|(d, (_, &c))| constant_squared_distance(query_constant, c, m).map_or(d, f32::sqrt))
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Inserts a discord keeping the list sorted by decreasing score, with no discords closer than
+/// `m` to each other and at most `k` of them.
+fn insert_discord(discords: &mut Vec<(usize, f32)>, discord: (usize, f32), k: usize, m: usize) {
+    if let Some(pos) = discords.iter().position(|o| discord.0 - o.0 < m) {
+        if discords[pos].1 >= discord.1 {
+            return;
+        }
+        discords.remove(pos);
+    }
+    let pos = discords
+        .iter()
+        .position(|o| o.1 < discord.1)
+        .unwrap_or(discords.len());
+    discords.insert(pos, discord);
+    discords.truncate(k);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        datasets::Dataset,
+        test_utils::random_data,
+        utils::{euclidean_distance, normalize},
+    };
+    use approx::assert_relative_eq;
+
+    /// Brute force left matrix profile.
+    fn left_profile(x: &[f32], m: usize) -> Vec<f32> {
+        let x = Array1::from(x.to_vec());
+        let n = x.len() - m + 1;
+        let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+        (0..n)
+            .map(|i| {
+                let a = normalize(x.slice(s![i..(i + m)]).to_owned());
+                (0..i.saturating_sub(exclusion_zone))
+                    .map(|j| {
+                        let b = normalize(x.slice(s![j..(j + m)]).to_owned());
+                        euclidean_distance(&a, &b)
+                    })
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_damp_top_discord() {
+        let data = Dataset::planted_discords(800, 20, 1, 34);
+        let (x, m, discord) = (data.values, data.m, data.discords[0]);
+        let split = discord / 2;
+        let res = DampDiscords::calculate(x.clone(), m, split, 1);
+        let expected = left_profile(&x, m);
+        let (idx, distance) =
+            expected
+                .iter()
+                .enumerate()
+                .skip(split)
+                .fold(
+                    (0, f32::NEG_INFINITY),
+                    |a, (i, &d)| {
+                        if d > a.1 {
+                            (i, d)
+                        } else {
+                            a
+                        }
+                    },
+                );
+        assert_eq!(res.get_discords(), &vec![idx]);
+        assert_relative_eq!(res.get_discord_distances()[0], distance, epsilon = 1e-2);
+        assert!(idx.abs_diff(discord) < m, "discord at {}", idx);
+    }
+
+    #[test]
+    fn test_damp_random() {
+        let m = 10;
+        let x = random_data(400, 34);
+        let res = DampDiscords::calculate(x.clone(), m, 100, 3);
+        let expected = left_profile(&x, m);
+        assert_eq!(res.get_discords().len(), 3);
+        for (&i, &d) in res.get_discords().iter().zip(res.get_discord_distances()) {
+            assert_relative_eq!(d, expected[i], epsilon = 1e-2);
+        }
+        let top = expected[100..]
+            .iter()
+            .cloned()
+            .fold(f32::NEG_INFINITY, f32::max);
+        assert_relative_eq!(res.get_discord_distances()[0], top, epsilon = 1e-2);
+        assert!(res.get_discords().windows(2).all(|o| o[0] != o[1]));
+    }
+}
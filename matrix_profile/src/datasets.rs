@@ -0,0 +1,310 @@
+//! Synthetic time series with planted motifs, discords and regime changes, returning the ground
+//! truth so tests and benchmarks can check the patterns are actually found.
+//!
+//! All the generators are deterministic for a given `seed`.
+use rand::{distributions::Uniform, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::f64::consts::PI;
+
+/// Generated series with the positions of the planted patterns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dataset {
+    pub values: Vec<f32>,
+    /// Length of the planted patterns, the window to search them with.
+    pub m: usize,
+    /// Start of the occurrences of each planted motif.
+    pub motifs: Vec<Vec<usize>>,
+    /// Start of each planted anomaly.
+    pub discords: Vec<usize>,
+    /// Positions where the generating process changes.
+    pub regime_changes: Vec<usize>,
+}
+
+fn rng(seed: u64) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(seed)
+}
+
+/// Standard normal sample with the Box-Muller transform.
+fn normal<R: Rng>(rng: &mut R) -> f64 {
+    let u = 1.0 - rng.gen::<f64>();
+    let v = rng.gen::<f64>();
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
+
+/// Independent values uniformly distributed between `low` and `high`.
+pub fn uniform_noise(n: usize, low: f32, high: f32, seed: u64) -> Vec<f32> {
+    rng(seed)
+        .sample_iter(Uniform::new_inclusive(low, high))
+        .take(n)
+        .collect()
+}
+
+/// Cumulative sum of standard normal steps, the usual benchmark data for matrix profiles.
+pub fn random_walk(n: usize, seed: u64) -> Vec<f32> {
+    let mut rng = rng(seed);
+    let mut v = 0f64;
+    (0..n)
+        .map(|_| {
+            v += normal(&mut rng);
+            v as f32
+        })
+        .collect()
+}
+
+/// Sum of sines with the given `periods` (in samples) and random phases, plus gaussian noise
+/// with standard deviation `noise`.
+pub fn sine_mixture(n: usize, periods: &[f32], noise: f32, seed: u64) -> Vec<f32> {
+    let mut rng = rng(seed);
+    let phases = periods
+        .iter()
+        .map(|_| rng.gen::<f64>() * 2.0 * PI)
+        .collect::<Vec<_>>();
+    (0..n)
+        .map(|i| {
+            let v = periods
+                .iter()
+                .zip(phases.iter())
+                .map(|(&p, &phase)| (2.0 * PI * i as f64 / p as f64 + phase).sin())
+                .sum::<f64>();
+            (v + normal(&mut rng) * noise as f64) as f32
+        })
+        .collect()
+}
+
+/// Waves of a heartbeat as `(position, width, amplitude)` gaussians, positions and widths as
+/// fractions of the beat.
+const NORMAL_BEAT: [(f64, f64, f64); 5] = [
+    (0.2, 0.025, 0.15),  // P
+    (0.35, 0.01, -0.15), // Q
+    (0.38, 0.012, 1.0),  // R
+    (0.41, 0.01, -0.25), // S
+    (0.65, 0.045, 0.3),  // T
+];
+
+/// Premature ventricular contraction: no P wave, wide QRS and inverted T wave.
+const ABNORMAL_BEAT: [(f64, f64, f64); 3] =
+    [(0.3, 0.04, 1.3), (0.4, 0.04, -0.5), (0.62, 0.06, -0.35)];
+
+fn beat(waves: &[(f64, f64, f64)], t: f64) -> f64 {
+    waves
+        .iter()
+        .map(|&(mu, sigma, a)| a * (-(t - mu).powi(2) / (2.0 * sigma * sigma)).exp())
+        .sum()
+}
+
+impl Dataset {
+    /// Random walk with a pattern of length `m` planted at `occurrences` non overlapping
+    /// positions, with a little noise on each copy.
+    pub fn planted_motif(n: usize, m: usize, occurrences: usize, seed: u64) -> Self {
+        assert!(occurrences >= 2, "A motif needs at least two occurrences");
+        assert!(
+            n >= occurrences * 3 * m,
+            "The series is too short for the occurrences"
+        );
+        let mut values = random_walk(n, seed);
+        let mut rng = rng(seed ^ 0x6d6f74);
+        // The pattern is a random walk of its own, scaled like the steps of the series
+        let pattern = random_walk(m, seed.wrapping_add(1));
+        let starts = spread_positions(&mut rng, n, m, occurrences);
+        for &start in starts.iter() {
+            let offset = values[start] - pattern[0];
+            for (i, &p) in pattern.iter().enumerate() {
+                values[start + i] = p + offset + (normal(&mut rng) * 0.01) as f32;
+            }
+        }
+        Self {
+            values,
+            m,
+            motifs: vec![starts],
+            discords: Vec::new(),
+            regime_changes: Vec::new(),
+        }
+    }
+
+    /// Mixture of sines with `count` anomalies of length `m`, each a burst of noise with the
+    /// amplitude of the series.
+    pub fn planted_discords(n: usize, m: usize, count: usize, seed: u64) -> Self {
+        assert!(
+            n >= (count + 1) * 4 * m,
+            "The series is too short for the anomalies"
+        );
+        let period = (m as f32 * 1.5).max(4.0);
+        let mut values = sine_mixture(n, &[period, period / 2.7], 0.01, seed);
+        let mut rng = rng(seed ^ 0x646973);
+        // Skip the borders, where the profile of a sine has no neighbour on one side
+        let starts = spread_positions(&mut rng, n - 2 * m, m, count)
+            .into_iter()
+            .map(|o| o + m)
+            .collect::<Vec<_>>();
+        for &start in starts.iter() {
+            for v in values[start..(start + m)].iter_mut() {
+                *v = (rng.gen::<f64>() * 3.0 - 1.5) as f32;
+            }
+        }
+        Self {
+            values,
+            m,
+            motifs: Vec::new(),
+            discords: starts,
+            regime_changes: Vec::new(),
+        }
+    }
+
+    /// ECG-like series of heartbeats every `beat_period` samples (with a few percent of jitter),
+    /// with one abnormal beat planted after the first quarter. The window is one beat.
+    pub fn ecg(n: usize, beat_period: usize, seed: u64) -> Self {
+        assert!(
+            beat_period >= 20 && n >= beat_period * 8,
+            "The series must have at least 8 beats of 20 samples"
+        );
+        let mut rng = rng(seed);
+        let mut lengths = Vec::new();
+        let mut total = 0;
+        while total < n {
+            let jitter = 1.0 + (rng.gen::<f64>() - 0.5) * 0.06;
+            let len = (beat_period as f64 * jitter).round() as usize;
+            lengths.push(len);
+            total += len;
+        }
+        let beats = lengths.len();
+        let abnormal = rng.gen_range((beats / 4)..(beats * 3 / 4));
+
+        let mut values = Vec::with_capacity(total);
+        let mut discord = 0;
+        for (b, &len) in lengths.iter().enumerate() {
+            let waves: &[_] = if b == abnormal {
+                discord = values.len();
+                &ABNORMAL_BEAT
+            } else {
+                &NORMAL_BEAT
+            };
+            for i in 0..len {
+                let v = beat(waves, i as f64 / len as f64) + normal(&mut rng) * 0.01;
+                values.push(v as f32);
+            }
+        }
+        values.truncate(n);
+        Self {
+            values,
+            m: beat_period,
+            motifs: Vec::new(),
+            discords: vec![discord],
+            regime_changes: Vec::new(),
+        }
+    }
+
+    /// Concatenation of `regimes` segments of similar length from different periodic
+    /// processes (sines and square waves of several periods), the window is a period.
+    pub fn regime_changes(n: usize, m: usize, regimes: usize, seed: u64) -> Self {
+        assert!(regimes >= 2, "At least two regimes are needed");
+        assert!(
+            n >= regimes * 10 * m,
+            "Each regime must be at least 10 windows long"
+        );
+        let noise = uniform_noise(n, -0.01, 0.01, seed);
+        let segment = n / regimes;
+        let regime_changes = (1..regimes).map(|r| r * segment).collect::<Vec<_>>();
+        let values = (0..n)
+            .map(|i| {
+                let r = (i / segment).min(regimes - 1);
+                // Alternating shapes with periods growing between regimes
+                let period = m as f64 * (1.0 + 0.5 * (r / 2) as f64);
+                let phase = 2.0 * PI * i as f64 / period;
+                let v = if r.is_multiple_of(2) {
+                    phase.sin()
+                } else {
+                    phase.sin().signum()
+                };
+                v as f32 + noise[i]
+            })
+            .collect::<Vec<_>>();
+        Self {
+            values,
+            m,
+            motifs: Vec::new(),
+            discords: Vec::new(),
+            regime_changes,
+        }
+    }
+}
+
+/// `count` sorted random starts of subsequences of length `m` in `0..n`, at least `2 * m` apart.
+fn spread_positions<R: Rng>(rng: &mut R, n: usize, m: usize, count: usize) -> Vec<usize> {
+    // Each position gets a slot, the free room is shared randomly between the gaps
+    let slot = 2 * m;
+    let room = n - count * slot;
+    let mut cuts = (0..count)
+        .map(|_| rng.gen_range(0..=room))
+        .collect::<Vec<_>>();
+    cuts.sort_unstable();
+    cuts.iter()
+        .enumerate()
+        .map(|(i, &cut)| cut + i * slot)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        motifs::{top_discords, top_motifs},
+        segmentation::{corrected_arc_curve, regime_changes},
+        DampDiscords, MatrixProfile, StompMatrixProfile,
+    };
+
+    #[test]
+    fn test_generators() {
+        assert_eq!(random_walk(100, 34), random_walk(100, 34));
+        assert_ne!(random_walk(100, 34), random_walk(100, 35));
+        let x = uniform_noise(1000, -1.0, 1.0, 34);
+        assert!(x.iter().all(|o| (-1.0..=1.0).contains(o)));
+        let x = sine_mixture(1000, &[50.0], 0.0, 34);
+        assert!((x[0] - x[50]).abs() < 1e-4);
+        let positions = spread_positions(&mut rng(34), 100, 10, 5);
+        assert!(positions.windows(2).all(|o| o[1] - o[0] >= 20));
+        assert!(positions.iter().all(|&o| o + 10 <= 100));
+    }
+
+    #[test]
+    fn test_planted_motif() {
+        let data = Dataset::planted_motif(2000, 50, 3, 34);
+        let starts = &data.motifs[0];
+        assert_eq!(starts.len(), 3);
+        let mp = StompMatrixProfile::calculate(data.values, data.m);
+        let motif = top_motifs(mp.get_profile(), mp.get_profile_idxs(), data.m, 1)[0];
+        assert!(starts.contains(&motif.idx) && starts.contains(&motif.neighbour));
+    }
+
+    #[test]
+    fn test_planted_discords() {
+        let data = Dataset::planted_discords(3000, 40, 2, 34);
+        let mp = StompMatrixProfile::calculate(data.values.clone(), data.m);
+        let found = top_discords(mp.get_profile(), data.m, 2);
+        for &start in data.discords.iter() {
+            assert!(
+                found.iter().any(|o| o.0.abs_diff(start) < data.m),
+                "discord at {} not found in {:?}",
+                start,
+                found
+            );
+        }
+    }
+
+    #[test]
+    fn test_ecg() {
+        let data = Dataset::ecg(4000, 100, 34);
+        assert_eq!(data.values.len(), 4000);
+        let split = data.discords[0] / 2;
+        let res = DampDiscords::calculate(data.values, data.m, split, 1);
+        assert!(res.get_discords()[0].abs_diff(data.discords[0]) < data.m);
+    }
+
+    #[test]
+    fn test_regime_changes() {
+        let data = Dataset::regime_changes(3000, 30, 2, 34);
+        let mp = StompMatrixProfile::calculate(data.values, data.m);
+        let cac = corrected_arc_curve(mp.get_profile_idxs(), data.m);
+        let change = regime_changes(&cac, data.m, 1)[0];
+        assert!(change.abs_diff(data.regime_changes[0]) < 2 * data.m);
+    }
+}
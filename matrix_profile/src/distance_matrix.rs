@@ -0,0 +1,205 @@
+//! Lazy access to the full distance matrix, for series where the `n × n` matrix computed by
+//! [`crate::NaiveMatrixProfile::calculate_full_matrix`] doesn't fit in memory.
+//!
+//! Rows and tiles are computed on demand with the STOMP recurrence, and [`DistanceMatrix::pooled`]
+//! aggregates the whole matrix into a small one, e.g. to render heatmaps.
+use crate::{
+    stomp::StompRows,
+    tiles::{tile_distances, Tile},
+};
+use ndarray::prelude::*;
+
+/// How the cells of a pooled matrix aggregate the distances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pooling {
+    /// Nearest distance, highlights motifs.
+    Min,
+    /// Farthest distance, highlights discords.
+    Max,
+}
+
+/// Distance matrix of the subsequences of a time series, with the same values as
+/// [`crate::NaiveMatrixProfile::calculate_full_matrix`]: the "exclusion zone" and the
+/// subsequences touching missing values are infinite.
+pub struct DistanceMatrix<'a> {
+    x: &'a [f32],
+    /// Window size.
+    m: usize,
+}
+
+impl<'a> DistanceMatrix<'a> {
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    pub fn new(x: &'a [f32], m: usize) -> Self {
+        assert!(
+            m > 0 && x.len() >= m,
+            "The series must be at least as long as the window"
+        );
+        Self { x, m }
+    }
+
+    /// Number of subsequences (rows and columns of the matrix).
+    pub fn len(&self) -> usize {
+        self.x.len() - self.m + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    /// Distances of the subsequence `i` to all the subsequences.
+    pub fn row(&self, i: usize) -> Array1<f32> {
+        let n = self.len();
+        assert!(i < n, "Row {} is out of the matrix", i);
+        self.tile(Tile::new(i..(i + 1), 0..n)).row(0).to_owned()
+    }
+
+    /// Iterates over all the rows in order, updating the sliding dot products between rows so
+    /// only one row is kept in memory.
+    pub fn rows(&self) -> DistanceRows {
+        DistanceRows {
+            rows: StompRows::new(self.x.to_vec(), self.m),
+        }
+    }
+
+    /// Distances of the cells of a tile, as a `rows × cols` array.
+    pub fn tile(&self, tile: Tile) -> Array2<f32> {
+        let shape = (tile.row_end - tile.row_start, tile.col_end - tile.col_start);
+        let mut res = Array2::from_elem(shape, f32::INFINITY);
+        tile_distances(self.x, self.m, tile, |i, j, d| res[[i, j]] = d.sqrt());
+        res
+    }
+
+    /// Downsamples the matrix to `shape`, each cell aggregating the finite distances of an even
+    /// block of rows and columns. Cells without finite distances (e.g. within the "exclusion
+    /// zone") are infinite.
+    ///
+    /// Only one row of the full matrix is kept in memory at a time.
+    pub fn pooled(&self, shape: (usize, usize), pooling: Pooling) -> Array2<f32> {
+        let n = self.len();
+        let (rows_out, cols_out) = shape;
+        assert!(
+            (1..=n).contains(&rows_out) && (1..=n).contains(&cols_out),
+            "The pooled shape must be between 1 and the matrix size"
+        );
+        let (init, better): (f32, fn(f32, f32) -> bool) = match pooling {
+            Pooling::Min => (f32::INFINITY, |a, b| a < b),
+            Pooling::Max => (f32::NEG_INFINITY, |a, b| a > b),
+        };
+        let col_bins = (0..n).map(|j| j * cols_out / n).collect::<Vec<_>>();
+        let mut res = Array2::from_elem(shape, init);
+
+        let mut rows = StompRows::new(self.x.to_vec(), self.m);
+        while let Some((i, distances)) = rows.next_row() {
+            let mut row = res.row_mut(i * rows_out / n);
+            for (&d, &bin) in distances.iter().zip(col_bins.iter()) {
+                if d.is_finite() && better(d, row[bin]) {
+                    row[bin] = d;
+                }
+            }
+        }
+
+        res.mapv_inplace(|o| {
+            if o.is_finite() {
+                o.sqrt()
+            } else {
+                f32::INFINITY
+            }
+        });
+        res
+    }
+}
+
+/// Rows of a [`DistanceMatrix`], see [`DistanceMatrix::rows`].
+pub struct DistanceRows {
+    rows: StompRows,
+}
+
+impl Iterator for DistanceRows {
+    type Item = Array1<f32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows
+            .next_row()
+            .map(|(_, distances)| distances.iter().map(|o| o.sqrt()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{naive::NaiveMatrixProfile, test_utils::random_data};
+    use approx::assert_relative_eq;
+
+    fn full_matrix(x: &[f32], m: usize) -> Array2<f32> {
+        NaiveMatrixProfile::calculate_full_matrix(x.to_vec(), m)
+            .get_full_matrix()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_rows_and_tiles() {
+        let mut x = random_data(80, 34);
+        x[50] = f32::NAN;
+        let m = 8;
+        let expected = full_matrix(&x, m);
+        let matrix = DistanceMatrix::new(&x, m);
+        assert_eq!(matrix.len(), 73);
+
+        let rows = matrix.rows().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 73);
+        for (i, row) in rows.iter().enumerate() {
+            let expected_row = expected.row(i).to_vec();
+            assert_relative_eq!(
+                row.as_slice().unwrap(),
+                expected_row.as_slice(),
+                epsilon = 1e-3
+            );
+        }
+        let (row, expected_row) = (matrix.row(20), expected.row(20).to_vec());
+        assert_relative_eq!(
+            row.as_slice().unwrap(),
+            expected_row.as_slice(),
+            epsilon = 1e-3
+        );
+
+        let tile = matrix.tile(Tile::new(10..30, 40..73));
+        let expected_tile = expected.slice(s![10..30, 40..73]).to_owned();
+        assert_relative_eq!(
+            tile.as_slice().unwrap(),
+            expected_tile.as_slice().unwrap(),
+            epsilon = 1e-3
+        );
+    }
+
+    #[test]
+    fn test_pooled() {
+        let x = random_data(60, 34);
+        let m = 6;
+        let expected = full_matrix(&x, m);
+        let n = expected.nrows();
+        let matrix = DistanceMatrix::new(&x, m);
+        for &pooling in [Pooling::Min, Pooling::Max].iter() {
+            let res = matrix.pooled((7, 10), pooling);
+            assert_eq!(res.dim(), (7, 10));
+            for ((r, c), &v) in res.indexed_iter() {
+                let block = expected
+                    .indexed_iter()
+                    .filter(|((i, j), d)| i * 7 / n == r && j * 10 / n == c && d.is_finite())
+                    .map(|(_, &d)| d);
+                let e = match pooling {
+                    Pooling::Min => block.fold(f32::INFINITY, f32::min),
+                    Pooling::Max => block.fold(f32::NEG_INFINITY, f32::max),
+                };
+                assert_relative_eq!(v, e, epsilon = 1e-3);
+            }
+        }
+    }
+}
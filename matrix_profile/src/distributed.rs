@@ -0,0 +1,422 @@
+//! Distributes the tiles of a matrix profile computation to worker processes.
+//!
+//! The series is read by the workers from a file of raw `f32` values (see [`MmapSeries`]), so
+//! only the tile coordinates and the [`PartialProfile`]s go through the connections. A worker
+//! is any process running [`serve_worker`] on a pair of streams, e.g. its stdin/stdout or a Unix
+//! socket. The `tile_worker` binary does both: [`Coordinator::spawn`] runs it without arguments,
+//! and `tile_worker --socket <path>` listens for [`Coordinator::connect`].
+//!
+//! The protocol is a sequence of little endian messages: the coordinator sends a [`TileJob`] and
+//! the worker answers with the number of partial profiles followed by the partial profiles.
+use crate::{
+    tiles::{compute_tile, MmapSeries, PartialProfile, Tile},
+    utils::{read_usize, write_usize},
+};
+use std::{
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::Mutex,
+    thread,
+};
+
+/// Longest path accepted in a [`TileJob`], so a corrupt message can't request a huge allocation.
+const MAX_PATH_LEN: usize = 4096;
+
+/// Tile to be computed by a worker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileJob {
+    /// File with the time series as raw native endian `f32` values.
+    pub path: PathBuf,
+    /// Window size.
+    pub m: usize,
+    pub tile: Tile,
+}
+
+impl TileJob {
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let path = self.path.to_str().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "The path is not valid UTF-8")
+        })?;
+        write_usize(w, path.len())?;
+        w.write_all(path.as_bytes())?;
+        write_usize(w, self.m)?;
+        let tile = self.tile;
+        for &v in [tile.row_start, tile.row_end, tile.col_start, tile.col_end].iter() {
+            write_usize(w, v)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the next job, returns `None` when the stream is closed before a new job.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Option<Self>> {
+        let len = match read_usize(r) {
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if len > MAX_PATH_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The path is too long",
+            ));
+        }
+        let mut path = vec![0u8; len];
+        r.read_exact(&mut path)?;
+        let path = String::from_utf8(path).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "The path is not valid UTF-8")
+        })?;
+        let m = read_usize(r)?;
+        if m < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid window"));
+        }
+        let (row_start, row_end) = (read_usize(r)?, read_usize(r)?);
+        let (col_start, col_end) = (read_usize(r)?, read_usize(r)?);
+        if row_start >= row_end || col_start >= col_end {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid tile"));
+        }
+        Ok(Some(Self {
+            path: path.into(),
+            m,
+            tile: Tile::new(row_start..row_end, col_start..col_end),
+        }))
+    }
+}
+
+/// Answers the jobs received on `input` until it is closed.
+pub fn serve_worker<R: Read, W: Write>(input: R, output: W) -> io::Result<()> {
+    let mut input = BufReader::new(input);
+    let mut output = BufWriter::new(output);
+    // Consecutive jobs usually use the same series
+    let mut series: Option<(PathBuf, MmapSeries)> = None;
+    while let Some(job) = TileJob::read_from(&mut input)? {
+        if !matches!(&series, Some((path, _)) if path == &job.path) {
+            series = Some((job.path.clone(), MmapSeries::open(&job.path)?));
+        }
+        let x = series.as_ref().unwrap().1.as_slice();
+        let end = job
+            .tile
+            .row_end
+            .max(job.tile.col_end)
+            .checked_add(job.m - 1);
+        if end.is_none_or(|end| end > x.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The tile is out of the distance matrix",
+            ));
+        }
+        let partials = compute_tile(x, job.m, job.tile);
+        write_usize(&mut output, partials.len())?;
+        for partial in partials.iter() {
+            partial.write_to(&mut output)?;
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+struct Connection {
+    input: Box<dyn Read + Send>,
+    output: Box<dyn Write + Send>,
+}
+
+/// Sends tiles to a set of workers, keeping one job in flight per worker, and merges their
+/// partial profiles.
+pub struct Coordinator {
+    connections: Vec<Connection>,
+    children: Vec<Child>,
+}
+
+impl Coordinator {
+    /// Uses already connected workers, given as (worker output, worker input) pairs.
+    pub fn new(connections: Vec<(Box<dyn Read + Send>, Box<dyn Write + Send>)>) -> Self {
+        assert!(!connections.is_empty(), "At least one worker is needed");
+        Self {
+            connections: connections
+                .into_iter()
+                .map(|(input, output)| Connection {
+                    input: Box::new(BufReader::new(input)),
+                    output: Box::new(BufWriter::new(output)),
+                })
+                .collect(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Spawns `workers` processes running `command`, talking to them through their stdin/stdout.
+    pub fn spawn(command: &mut Command, workers: usize) -> io::Result<Self> {
+        let mut connections: Vec<(Box<dyn Read + Send>, Box<dyn Write + Send>)> = Vec::new();
+        let mut children = Vec::new();
+        for _ in 0..workers {
+            let mut child = command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?;
+            let output = child.stdin.take().unwrap();
+            let input = child.stdout.take().unwrap();
+            connections.push((Box::new(input), Box::new(output)));
+            children.push(child);
+        }
+        let mut coordinator = Self::new(connections);
+        coordinator.children = children;
+        Ok(coordinator)
+    }
+
+    /// Connects to workers listening on Unix sockets.
+    #[cfg(unix)]
+    pub fn connect<P: AsRef<Path>>(sockets: &[P]) -> io::Result<Self> {
+        let connections = sockets
+            .iter()
+            .map(|path| {
+                let stream = std::os::unix::net::UnixStream::connect(path)?;
+                let input: Box<dyn Read + Send> = Box::new(stream.try_clone()?);
+                let output: Box<dyn Write + Send> = Box::new(stream);
+                Ok((input, output))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self::new(connections))
+    }
+
+    /// Computes the full matrix profile of the series stored at `path`, splitting the distance
+    /// matrix in tiles of `tile_size` subsequences (see [`Tile::cover`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File with the time series as raw native endian `f32` values
+    /// * `m` - Window size
+    /// * `tile_size` - Number of subsequences on each side of the tiles
+    pub fn run<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        m: usize,
+        tile_size: usize,
+    ) -> io::Result<PartialProfile> {
+        let path = path.as_ref().to_path_buf();
+        let len = MmapSeries::open(&path)?.as_slice().len();
+        if m < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The window must be at least 2",
+            ));
+        }
+        if len < m {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The series is shorter than the window",
+            ));
+        }
+        let n = len - m + 1;
+        let tiles = Mutex::new(Tile::cover(n, tile_size));
+        // Single accumulator, each partial only locks it to update its own columns
+        let merged = Mutex::new(PartialProfile::empty(Tile::new(0..n, 0..n), m));
+
+        let results = thread::scope(|s| {
+            let handles = self
+                .connections
+                .iter_mut()
+                .map(|connection| {
+                    let (tiles, path, merged) = (&tiles, &path, &merged);
+                    s.spawn(move || -> io::Result<()> {
+                        loop {
+                            // The lock must be released before waiting for the worker
+                            let tile = tiles.lock().unwrap().pop();
+                            let tile = match tile {
+                                Some(tile) => tile,
+                                None => break,
+                            };
+                            let job = TileJob {
+                                path: path.clone(),
+                                m,
+                                tile,
+                            };
+                            job.write_to(&mut connection.output)?;
+                            connection.output.flush()?;
+                            let count = read_usize(&mut connection.input)?;
+                            if count > 2 {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "Too many partial profiles for a tile",
+                                ));
+                            }
+                            for _ in 0..count {
+                                let partial = PartialProfile::read_from(&mut connection.input)?;
+                                check_partial(&partial, &job)?;
+                                merged.lock().unwrap().merge_into(&partial);
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|o| o.join().expect("Worker thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        results.into_iter().collect::<io::Result<()>>()?;
+        Ok(merged.into_inner().unwrap())
+    }
+}
+
+/// Checks a partial profile received from a worker answers `job`, so it can be merged.
+fn check_partial(partial: &PartialProfile, job: &TileJob) -> io::Result<()> {
+    let exclusion_zone = (job.m as f32 / 4f32).ceil() as usize;
+    let tile = partial.get_tile();
+    if partial.get_window_size() != job.m
+        || partial.get_exclusion_zone() != exclusion_zone
+        || (tile != job.tile && tile != job.tile.transpose())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The partial profile doesn't match the job",
+        ));
+    }
+    Ok(())
+}
+
+impl Drop for Coordinator {
+    fn drop(&mut self) {
+        // Closing the workers input makes them exit
+        self.connections.clear();
+        for child in self.children.iter_mut() {
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix_profile::MatrixProfile, stomp::StompMatrixProfile, test_utils::random_data,
+        tiles::calculate_tiled,
+    };
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_job_encoding() {
+        let job = TileJob {
+            path: "/tmp/series.f32".into(),
+            m: 10,
+            tile: Tile::new(0..20, 40..60),
+        };
+        let mut bytes = Vec::new();
+        job.write_to(&mut bytes).unwrap();
+        job.write_to(&mut bytes).unwrap();
+        let mut r = bytes.as_slice();
+        assert_eq!(TileJob::read_from(&mut r).unwrap(), Some(job.clone()));
+        assert_eq!(TileJob::read_from(&mut r).unwrap(), Some(job.clone()));
+        assert_eq!(TileJob::read_from(&mut r).unwrap(), None);
+
+        // Corrupt messages are rejected before allocating or computing anything
+        let mut bytes = Vec::new();
+        write_usize(&mut bytes, usize::MAX).unwrap();
+        let err = TileJob::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        for &m in [0, 1].iter() {
+            let mut bytes = Vec::new();
+            TileJob { m, ..job.clone() }.write_to(&mut bytes).unwrap();
+            let err = TileJob::read_from(&mut bytes.as_slice()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_worker_rejects_invalid_jobs() {
+        let x = random_data(50, 34);
+        let path = std::env::temp_dir().join(format!("mp_invalid_job_{}.f32", std::process::id()));
+        let bytes = x.iter().flat_map(|o| o.to_ne_bytes()).collect::<Vec<_>>();
+        std::fs::write(&path, bytes).unwrap();
+        for &(m, end) in [(usize::MAX, 10), (10, 42)].iter() {
+            let job = TileJob {
+                path: path.clone(),
+                m,
+                tile: Tile::new(0..10, 0..end),
+            };
+            let mut input = Vec::new();
+            job.write_to(&mut input).unwrap();
+            let err = serve_worker(input.as_slice(), Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_coordinator_rejects_invalid_partials() {
+        use std::os::unix::net::UnixStream;
+
+        let x = random_data(100, 34);
+        let path =
+            std::env::temp_dir().join(format!("mp_invalid_partial_{}.f32", std::process::id()));
+        let bytes = x.iter().flat_map(|o| o.to_ne_bytes()).collect::<Vec<_>>();
+        std::fs::write(&path, bytes).unwrap();
+
+        // Answers the first job with a partial of another window or out of the distance matrix
+        let answers: [fn(Tile) -> PartialProfile; 2] = [
+            |tile| PartialProfile::empty(tile, 12),
+            |tile| PartialProfile::empty(Tile::new(tile.row_start..tile.row_end, 0..500), 10),
+        ];
+        for &answer in answers.iter() {
+            let (coordinator_side, mut worker_side) = UnixStream::pair().unwrap();
+            let worker = thread::spawn(move || {
+                let job = TileJob::read_from(&mut worker_side).unwrap().unwrap();
+                write_usize(&mut worker_side, 1).unwrap();
+                answer(job.tile).write_to(&mut worker_side).unwrap();
+            });
+            let connections: Vec<(Box<dyn Read + Send>, Box<dyn Write + Send>)> = vec![(
+                Box::new(coordinator_side.try_clone().unwrap()),
+                Box::new(coordinator_side),
+            )];
+            let err = Coordinator::new(connections)
+                .run(&path, 10, 100)
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            worker.join().unwrap();
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_coordinator() {
+        use std::os::unix::net::UnixStream;
+
+        let x = random_data(300, 34);
+        let path = std::env::temp_dir().join(format!("mp_distributed_{}.f32", std::process::id()));
+        let bytes = x.iter().flat_map(|o| o.to_ne_bytes()).collect::<Vec<_>>();
+        std::fs::write(&path, bytes).unwrap();
+
+        // The result doesn't depend on the number of workers nor on the order of the tiles
+        let expected = calculate_tiled(&x, 10, 40);
+        for &count in [1, 3].iter() {
+            let mut workers = Vec::new();
+            let mut connections: Vec<(Box<dyn Read + Send>, Box<dyn Write + Send>)> = Vec::new();
+            for _ in 0..count {
+                let (coordinator_side, worker_side) = UnixStream::pair().unwrap();
+                workers.push(thread::spawn(move || {
+                    serve_worker(worker_side.try_clone().unwrap(), worker_side)
+                }));
+                connections.push((
+                    Box::new(coordinator_side.try_clone().unwrap()),
+                    Box::new(coordinator_side),
+                ));
+            }
+            let res = Coordinator::new(connections).run(&path, 10, 40).unwrap();
+            for worker in workers {
+                worker.join().unwrap().unwrap();
+            }
+            assert_eq!(res, expected, "{} workers", count);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let stomp = StompMatrixProfile::calculate(x, 10);
+        assert_relative_eq!(
+            expected.get_profile().as_slice(),
+            stomp.get_profile().as_slice(),
+            epsilon = 1e-3
+        );
+        assert_eq!(expected.get_profile_idxs(), stomp.get_profile_idxs());
+    }
+}
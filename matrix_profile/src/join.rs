@@ -0,0 +1,109 @@
+//! AB-join Matrix Profile: the nearest neighbour of each subsequence of a time series in another
+//! time series, useful to find the patterns shared (or not) by two series.
+use crate::{matrix_profile::NO_NEIGHBOUR, tiles::cross_distances};
+
+/// There is no "exclusion zone" as the subsequences come from different series.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoinMatrixProfile {
+    /// Distance of each subsequence of `a` to its nearest neighbour in `b`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::distances"))]
+    profile: Vec<f32>,
+    /// Index in `b` of the nearest neighbour of each subsequence of `a`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::indices"))]
+    profile_idxs: Vec<usize>,
+    /// Window size.
+    m: usize,
+}
+
+impl JoinMatrixProfile {
+    /// Computes the AB-join matrix profile using the STOMP recurrence.
+    ///
+    /// There is no "exclusion zone" as the subsequences come from different series, subsequences
+    /// touching missing values have an infinite distance and [`NO_NEIGHBOUR`] as index.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - Time series whose subsequences are searched
+    /// * `b` - Time series where the neighbours are searched
+    /// * `m` - Window size
+    pub fn calculate(a: Vec<f32>, b: Vec<f32>, m: usize) -> Self {
+        let n = a.len() + 1 - m;
+        let mut profile = vec![f32::INFINITY; n];
+        let mut profile_idxs = vec![NO_NEIGHBOUR; n];
+        cross_distances(&a, &b, m, None, |i, j, d| {
+            // Columns are processed in order, so equal distances keep the lowest index
+            if d < profile[i] {
+                profile[i] = d;
+                profile_idxs[i] = j;
+            }
+        });
+        profile.iter_mut().for_each(|o| *o = o.sqrt());
+
+        Self {
+            profile,
+            profile_idxs,
+            m,
+        }
+    }
+
+    pub fn get_profile(&self) -> &Vec<f32> {
+        &self.profile
+    }
+
+    pub fn get_profile_idxs(&self) -> &Vec<usize> {
+        &self.profile_idxs
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::random_data,
+        utils::{euclidean_distance, normalize},
+    };
+    use approx::assert_relative_eq;
+    use ndarray::prelude::*;
+
+    #[test]
+    fn test_join() {
+        let a = random_data(60, 34);
+        let mut b = random_data(90, 35);
+        b[40] = f32::NAN;
+        let m = 8;
+        let res = JoinMatrixProfile::calculate(a.clone(), b.clone(), m);
+        assert_eq!(res.get_profile().len(), 53);
+
+        let (a, b) = (Array1::from(a), Array1::from(b));
+        for (i, (&d, &j)) in res
+            .get_profile()
+            .iter()
+            .zip(res.get_profile_idxs())
+            .enumerate()
+        {
+            let q = normalize(a.slice(s![i..(i + m)]).to_owned());
+            let (expected_j, expected_d) = (0..(b.len() + 1 - m))
+                .filter(|j| !(33..=40).contains(j))
+                .map(|j| {
+                    let t = normalize(b.slice(s![j..(j + m)]).to_owned());
+                    (j, euclidean_distance(&q, &t))
+                })
+                .fold(
+                    (NO_NEIGHBOUR, f32::INFINITY),
+                    |a, b| {
+                        if b.1 < a.1 {
+                            b
+                        } else {
+                            a
+                        }
+                    },
+                );
+            assert_relative_eq!(d, expected_d, epsilon = 1e-3);
+            assert_eq!(j, expected_j);
+        }
+    }
+}
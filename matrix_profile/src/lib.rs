@@ -6,16 +6,48 @@
 //!
 //! [talk]: https://www.youtube.com/watch?v=cCJy6hzAJQQ
 //! [here]: https://www.cs.ucr.edu/~eamonn/MatrixProfile.html
-#![allow(clippy::many_single_char_names)]
+#![allow(clippy::many_single_char_names, clippy::excessive_precision)]
 
+pub mod annotation;
+#[cfg(feature = "arrow")]
+pub mod arrow_io;
+pub mod damp;
+pub mod datasets;
+pub mod distance_matrix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod distributed;
+pub mod join;
+pub mod mass;
 pub mod matrix_profile;
+pub mod motifs;
 pub mod naive;
+#[cfg(feature = "npy")]
+pub mod npy;
+pub mod progress;
+pub mod segmentation;
+#[cfg(feature = "serde")]
+mod serde_utils;
+pub mod simd;
+pub mod stats;
 pub mod stomp;
+pub mod streaming;
+pub mod tiles;
+pub mod timeseries;
+pub mod topk;
 pub mod utils;
 
-pub use crate::matrix_profile::MatrixProfile;
+pub use crate::matrix_profile::{MatrixProfile, NO_NEIGHBOUR};
+pub use damp::DampDiscords;
+pub use distance_matrix::DistanceMatrix;
+pub use join::JoinMatrixProfile;
 pub use naive::NaiveMatrixProfile;
-pub use stomp::StompMatrixProfile;
+pub use progress::{CancellationToken, Outcome, ProgressObserver};
+pub use stomp::{StompMatrixProfile, StompWorkspace};
+pub use streaming::StreamingMatrixProfile;
+pub use timeseries::TimeSeries;
+pub use topk::TopKMatrixProfile;
 
+#[cfg(test)]
+mod cross_validation;
 #[cfg(test)]
 mod test_utils;
@@ -0,0 +1,144 @@
+//! Mueen's Algorithm for Similarity Search (MASS), computes the distances between a query and
+//! every subsequence of a time series using FFT, check [here] for more details.
+//!
+//! [here]: https://www.cs.unm.edu/~mueen/FastestSimilaritySearch.html
+use crate::stats::{precompute_stats, remove_mean};
+use ndarray::prelude::*;
+use realfft::{num_complex::Complex32, RealFftPlanner};
+
+/// Caches the FFT plans used by MASS, so repeated queries over series of the same length reuse
+/// them.
+pub struct MassPlanner {
+    planner: RealFftPlanner<f32>,
+}
+
+impl Default for MassPlanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MassPlanner {
+    pub fn new() -> Self {
+        Self {
+            planner: RealFftPlanner::new(),
+        }
+    }
+
+    /// Computes the dot products between the query `q` and every subsequence of `t` with the
+    /// same length.
+    pub fn sliding_dot_product(&mut self, q: ArrayView1<f32>, t: ArrayView1<f32>) -> Vec<f32> {
+        let n = t.len();
+        let m = q.len();
+        let fft = self.planner.plan_fft_forward(2 * n);
+        let ifft = self.planner.plan_fft_inverse(2 * n);
+
+        // Append t with n zeros
+        let mut ta = fft.make_input_vec();
+        ta.iter_mut().zip(t.iter()).for_each(|(a, &b)| *a = b);
+
+        // Reverse Q and append zeros
+        let mut qra = fft.make_input_vec();
+        qra.iter_mut()
+            .zip(q.iter().rev())
+            .for_each(|(a, &b)| *a = b);
+
+        // Compute the real FFTs, only the first half of the spectrum is kept
+        let mut taf = fft.make_output_vec();
+        fft.process(&mut ta, &mut taf).unwrap();
+        let mut qraf = fft.make_output_vec();
+        fft.process(&mut qra, &mut qraf).unwrap();
+
+        // Compute the inverse FFT to the element-wise multiplication of qraf and taf
+        qraf.iter_mut().zip(taf.iter()).for_each(|(a, b)| *a *= b);
+        // The spectrum of a real signal has real DC and Nyquist terms, drop the rounding noise
+        let last = qraf.len() - 1;
+        qraf[0] = Complex32::new(qraf[0].re, 0.0);
+        qraf[last] = Complex32::new(qraf[last].re, 0.0);
+        let mut qt = ifft.make_output_vec();
+        ifft.process(&mut qraf, &mut qt).unwrap();
+
+        let div = qt.len() as f32;
+        qt.into_iter()
+            .skip(m - 1)
+            .take(n - m + 1)
+            .map(|o| o / div)
+            .collect::<Vec<_>>()
+    }
+
+    /// Computes the z-normalized euclidean distances between the query `q` and every
+    /// subsequence of `t` with the same length.
+    pub fn mass(&mut self, q: ArrayView1<f32>, t: ArrayView1<f32>) -> Vec<f32> {
+        let m = q.len();
+        // Distances are shift invariant, centering keeps the dot products small
+        let q = remove_mean(&q.to_owned());
+        let t = remove_mean(&t.to_owned());
+        let qt = self.sliding_dot_product(q.view(), t.view());
+        let (mean_t, sigma_t) = precompute_stats(&t, m);
+        let mean_q = q.mean().unwrap();
+        let sigma_q = q.std_axis(Axis(0), 0.0).into_scalar().max(f32::EPSILON);
+
+        qt.iter()
+            .zip(mean_t.iter())
+            .zip(sigma_t.iter())
+            .map(|((t, mt), st)| {
+                2.0 * ((m as f32) - (t - (m as f32) * mt * mean_q) / (sigma_q * st))
+            })
+            .map(|o| o.max(0.0).sqrt())
+            .collect()
+    }
+}
+
+/// Computes the dot products between the query `q` and every subsequence of `t` with the same length.
+///
+/// Use a [`MassPlanner`] to reuse the FFT plans on repeated calls.
+pub fn sliding_dot_product(q: ArrayView1<f32>, t: ArrayView1<f32>) -> Vec<f32> {
+    MassPlanner::new().sliding_dot_product(q, t)
+}
+
+/// Computes the z-normalized euclidean distances between the query `q` and every subsequence of
+/// `t` with the same length.
+///
+/// Use a [`MassPlanner`] to reuse the FFT plans on repeated calls.
+pub fn mass(q: ArrayView1<f32>, t: ArrayView1<f32>) -> Vec<f32> {
+    MassPlanner::new().mass(q, t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::random_data,
+        utils::{euclidean_distance, normalize},
+    };
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_mass() {
+        let t = Array1::from(random_data(50, 34));
+        let q = Array1::from(random_data(8, 35));
+        let res = mass(q.view(), t.view());
+        let expected = (0..43)
+            .map(|i| {
+                let a = normalize(q.clone());
+                let b = normalize(t.slice(s![i..(i + 8)]).to_owned());
+                euclidean_distance(&a, &b)
+            })
+            .collect::<Vec<_>>();
+        assert_relative_eq!(res.as_slice(), expected.as_slice(), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_planner_reuse() {
+        let mut planner = MassPlanner::new();
+        let t = Array1::from(random_data(64, 34));
+        for seed in 0..4 {
+            let q = Array1::from(random_data(8, seed));
+            let res = planner.sliding_dot_product(q.view(), t.view());
+            let expected = (0..57)
+                .map(|i| q.dot(&t.slice(s![i..(i + 8)])))
+                .collect::<Vec<_>>();
+            assert_relative_eq!(res.as_slice(), expected.as_slice(), epsilon = 1e-2);
+        }
+    }
+}
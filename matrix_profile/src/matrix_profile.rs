@@ -1,11 +1,50 @@
 //! General trait for Matrix Profile calculation.
+use crate::{annotation::corrected_profile, utils::distance_to_correlation};
 
+/// Index used in the matrix profile indices when a subsequence has no valid neighbour,
+/// e.g. when it contains missing values.
+pub const NO_NEIGHBOUR: usize = usize::MAX;
+
+/// Matrix profile of a time series: the distance of each subsequence to its nearest neighbour,
+/// and the index of that neighbour.
+///
+/// # Ties
+///
+/// When several neighbours are at the same distance the lowest index is returned. Every
+/// algorithm follows this rule, and its results don't depend on the number of threads or
+/// workers, nor on the order partial results are merged in. Note that neighbours at the same
+/// theoretical distance (e.g. repetitions of a pattern) can get distances differing by rounding
+/// errors, which are not ties, and algorithms computing them differently can then disagree.
 pub trait MatrixProfile {
     /// # Arguments
     ///
-    /// * `x` - Time series data
+    /// * `x` - Time series data, missing values can be represented as NaN or infinite. The
+    ///   subsequences touching them have no neighbour, the constant ones are compared as in
+    ///   [`crate::utils::constant_squared_distance`]
     /// * `m` - Window size
     fn calculate(x: Vec<f32>, m: usize) -> Self;
     fn get_profile(&self) -> &Vec<f32>;
     fn get_profile_idxs(&self) -> &Vec<usize>;
+    fn get_window_size(&self) -> usize;
+    /// Nearby subsequences are not considered neighbours when they are at most this far apart.
+    fn get_exclusion_zone(&self) -> usize;
+
+    /// Matrix profile expressed as the Pearson correlation coefficient of each subsequence with
+    /// its nearest neighbour, subsequences without neighbour get `-inf`.
+    fn get_correlation_profile(&self) -> Vec<f32> {
+        let m = self.get_window_size();
+        self.get_profile()
+            .iter()
+            .map(|&d| distance_to_correlation(d, m))
+            .collect()
+    }
+
+    /// Computes the corrected matrix profile used for guided motif search.
+    ///
+    /// # Arguments
+    ///
+    /// * `av` - Annotation vector, check [`crate::annotation::AnnotationVector`]
+    fn get_corrected_profile(&self, av: &[f32]) -> Vec<f32> {
+        corrected_profile(self.get_profile(), av)
+    }
 }
@@ -0,0 +1,108 @@
+//! Motifs (most similar pairs of subsequences) and discords (subsequences farthest from their
+//! nearest neighbour) from a computed Matrix Profile.
+use crate::matrix_profile::NO_NEIGHBOUR;
+
+/// Pair of nearest neighbour subsequences.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Motif {
+    pub idx: usize,
+    pub neighbour: usize,
+    pub distance: f32,
+}
+
+/// Finds the `k` closest motifs, sorted by increasing distance.
+///
+/// Subsequences overlapping (closer than `m`) any subsequence of an already selected motif are
+/// skipped, so each motif is a different pattern. Equal distances keep the lowest index first.
+///
+/// # Arguments
+///
+/// * `profile` - Matrix profile
+/// * `profile_idxs` - Matrix profile indices
+/// * `m` - Window size
+/// * `k` - Number of motifs to find
+pub fn top_motifs(profile: &[f32], profile_idxs: &[usize], m: usize, k: usize) -> Vec<Motif> {
+    let mut order = (0..profile.len())
+        .filter(|&i| profile[i].is_finite() && profile_idxs[i] != NO_NEIGHBOUR)
+        .collect::<Vec<_>>();
+    order.sort_by(|&a, &b| profile[a].partial_cmp(&profile[b]).unwrap());
+
+    let mut motifs: Vec<Motif> = Vec::with_capacity(k);
+    for i in order {
+        if motifs.len() == k {
+            break;
+        }
+        let j = profile_idxs[i];
+        let overlaps = motifs.iter().any(|o| {
+            [o.idx, o.neighbour]
+                .iter()
+                .any(|&p| p.abs_diff(i) < m || p.abs_diff(j) < m)
+        });
+        if !overlaps {
+            motifs.push(Motif {
+                idx: i.min(j),
+                neighbour: i.max(j),
+                distance: profile[i],
+            });
+        }
+    }
+    motifs
+}
+
+/// Finds the `k` top discords, sorted by decreasing distance to their nearest neighbour.
+///
+/// Discords closer than `m` to an already selected one are skipped. Equal distances keep the
+/// lowest index first.
+///
+/// # Arguments
+///
+/// * `profile` - Matrix profile
+/// * `m` - Window size
+/// * `k` - Number of discords to find
+pub fn top_discords(profile: &[f32], m: usize, k: usize) -> Vec<(usize, f32)> {
+    let mut order = (0..profile.len())
+        .filter(|&i| profile[i].is_finite())
+        .collect::<Vec<_>>();
+    order.sort_by(|&a, &b| profile[b].partial_cmp(&profile[a]).unwrap());
+
+    let mut discords: Vec<(usize, f32)> = Vec::with_capacity(k);
+    for i in order {
+        if discords.len() == k {
+            break;
+        }
+        if discords.iter().all(|o| o.0.abs_diff(i) >= m) {
+            discords.push((i, profile[i]));
+        }
+    }
+    discords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{datasets::Dataset, matrix_profile::MatrixProfile, stomp::StompMatrixProfile};
+
+    #[test]
+    fn test_planted_motif() {
+        let data = Dataset::planted_motif(300, 20, 2, 34);
+        let mp = StompMatrixProfile::calculate(data.values, data.m);
+        let motifs = top_motifs(mp.get_profile(), mp.get_profile_idxs(), data.m, 3);
+        assert_eq!(motifs.len(), 3);
+        let starts = &data.motifs[0];
+        assert_eq!((motifs[0].idx, motifs[0].neighbour), (starts[0], starts[1]));
+        // The copies only differ by a little noise, far closer than any other pair
+        assert!(motifs[0].distance < motifs[1].distance / 5.0);
+        assert!(motifs.windows(2).all(|o| o[0].distance <= o[1].distance));
+    }
+
+    #[test]
+    fn test_discords() {
+        let profile = vec![1.0, 5.0, 4.0, 1.0, 1.0, 3.0, f32::INFINITY, 2.0];
+        assert_eq!(
+            top_discords(&profile, 2, 3),
+            vec![(1, 5.0), (5, 3.0), (7, 2.0)]
+        );
+        assert_eq!(top_discords(&profile, 1, 2), vec![(1, 5.0), (2, 4.0)]);
+    }
+}
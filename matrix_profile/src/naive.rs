@@ -1,66 +1,140 @@
 //! Naive implementation to calculate the Matrix Profile.
+//!
+//! It is the reference the other algorithms are tested against, so the distances are computed
+//! in double precision.
 use crate::{
-    matrix_profile::MatrixProfile,
-    utils::{euclidean_distance, normalize},
+    matrix_profile::{MatrixProfile, NO_NEIGHBOUR},
+    progress::{CancellationToken, Outcome, ProgressObserver},
+    utils::{
+        constant_squared_distance, constant_subsequences, euclidean_distance, normalize,
+        valid_subsequences,
+    },
 };
 use ndarray::prelude::*;
-use ndarray_stats::QuantileExt;
-use std::cmp::Ordering;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NaiveMatrixProfile {
     /// Matrix profile.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::distances"))]
     profile: Vec<f32>,
     /// Matrix profile indices.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::indices"))]
     profile_idxs: Vec<usize>,
-    #[allow(dead_code)]
     /// Window size.
     m: usize,
+    /// Nearby subsequences excluded as neighbours, at most this far apart.
+    exclusion_zone: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_utils::optional_distance_matrix")
+    )]
     full_matrix: Option<Array2<f32>>,
 }
 
 impl MatrixProfile for NaiveMatrixProfile {
     fn calculate(x: Vec<f32>, m: usize) -> Self {
+        Self::calculate_with_progress(x, m, &mut |_, _| {}, &CancellationToken::new()).into_inner()
+    }
+
+    fn get_profile(&self) -> &Vec<f32> {
+        &self.profile
+    }
+
+    fn get_profile_idxs(&self) -> &Vec<usize> {
+        &self.profile_idxs
+    }
+
+    fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    fn get_exclusion_zone(&self) -> usize {
+        self.exclusion_zone
+    }
+}
+
+impl NaiveMatrixProfile {
+    /// Computes the matrix profile using a naive (brute force) algorithm, reporting the progress
+    /// after each row and stopping early when `token` is cancelled.
+    ///
+    /// The rows not processed in a cancelled result have infinite distances and
+    /// [`NO_NEIGHBOUR`] as index.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `observer` - Receives the number of rows processed
+    /// * `token` - Cancellation token, checked between rows
+    pub fn calculate_with_progress(
+        x: Vec<f32>,
+        m: usize,
+        observer: &mut dyn ProgressObserver,
+        token: &CancellationToken,
+    ) -> Outcome<Self> {
         let x = Array1::from(x);
         let n = x.len() - m + 1;
         // Nearby subsequences are likely highly similar so we define an "exclusion zone" around the diagonal
         let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+        // Subsequences touching missing values are excluded
+        let valid = valid_subsequences(&x, m);
+        let constant = constant_subsequences(&x, m);
+        let mut profile = vec![f32::INFINITY; n];
+        let mut profile_idxs = vec![NO_NEIGHBOUR; n];
 
-        let (profile, profile_idxs) = (0..n)
-            .map(|i| {
-                let a = normalize(x.slice(s![i..(i + m)]).to_owned());
+        for i in 0..n {
+            if token.is_cancelled() {
+                let partial = Self {
+                    profile,
+                    profile_idxs,
+                    m,
+                    exclusion_zone,
+                    full_matrix: None,
+                };
+                return Outcome::Cancelled {
+                    partial,
+                    rows_done: i,
+                };
+            }
+            if valid[i] {
+                let a = normalize(x.slice(s![i..(i + m)]).mapv(f64::from));
                 let exclusion_start = i - exclusion_zone.min(i);
                 let exclusion_end = (i + exclusion_zone).min(n);
-                (0..n)
-                    .filter(|&j| (j < exclusion_start) || (exclusion_end < j))
+                let (d, j) = (0..n)
+                    .filter(|&j| valid[j] && ((j < exclusion_start) || (exclusion_end < j)))
                     .map(|j| {
-                        let b = normalize(x.slice(s![j..(j + m)]).to_owned());
-                        let distance = euclidean_distance(&a, &b);
+                        let distance = match constant_squared_distance(constant[i], constant[j], m)
+                        {
+                            Some(d) => d.sqrt(),
+                            None => {
+                                let b = normalize(x.slice(s![j..(j + m)]).mapv(f64::from));
+                                euclidean_distance(&a, &b) as f32
+                            }
+                        };
                         (distance, j)
                     })
-                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Less))
-                    .unwrap()
-            })
-            .unzip();
+                    // Candidates are visited in order, so equal distances keep the lowest index
+                    .fold(
+                        (f32::INFINITY, NO_NEIGHBOUR),
+                        |a, b| if b.0 < a.0 { b } else { a },
+                    );
+                profile[i] = d;
+                profile_idxs[i] = j;
+            }
+            observer.on_progress(i + 1, n);
+        }
 
-        Self {
+        Outcome::Completed(Self {
             profile,
             profile_idxs,
             m,
+            exclusion_zone,
             full_matrix: None,
-        }
+        })
     }
 
-    fn get_profile(&self) -> &Vec<f32> {
-        &self.profile
-    }
-
-    fn get_profile_idxs(&self) -> &Vec<usize> {
-        &self.profile_idxs
-    }
-}
-
-impl NaiveMatrixProfile {
-    /// Computes the matrix profile using a naive (brute force) algorithm.
+    /// Computes the matrix profile using a naive (brute force) algorithm, keeping the full
+    /// `n × n` distance matrix. For long series use [`crate::distance_matrix::DistanceMatrix`].
     ///
     /// # Arguments
     ///
@@ -71,24 +145,32 @@ impl NaiveMatrixProfile {
         let n = x.len() - m + 1;
         // Nearby subsequences are likely highly similar so we define an "exclusion zone" around the diagonal
         let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+        // Subsequences touching missing values are excluded
+        let valid = valid_subsequences(&x, m);
+        let constant = constant_subsequences(&x, m);
 
         let matrix = (0..n)
-            .map(|i| {
-                let a = normalize(x.slice(s![i..(i + m)]).to_owned());
+            .flat_map(|i| {
+                let a = normalize(x.slice(s![i..(i + m)]).mapv(f64::from));
                 let exclusion_start = i - exclusion_zone.min(i);
                 let exclusion_end = (i + exclusion_zone).min(n);
                 (0..n)
                     .map(|j| {
+                        if !valid[i] || !valid[j] {
+                            return f32::INFINITY;
+                        }
                         if (exclusion_start <= j) && (j <= exclusion_end) {
                             return f32::INFINITY;
                         }
 
-                        let b = normalize(x.slice(s![j..(j + m)]).to_owned());
-                        euclidean_distance(&a, &b)
+                        if let Some(d) = constant_squared_distance(constant[i], constant[j], m) {
+                            return d.sqrt();
+                        }
+                        let b = normalize(x.slice(s![j..(j + m)]).mapv(f64::from));
+                        euclidean_distance(&a, &b) as f32
                     })
                     .collect::<Vec<_>>()
             })
-            .flatten()
             .collect::<Vec<_>>();
         let matrix = Array::from_shape_vec((n, n), matrix).unwrap();
         let (profile, profile_idxs) = profile_from_matrix(&matrix);
@@ -97,6 +179,7 @@ impl NaiveMatrixProfile {
             profile,
             profile_idxs,
             m,
+            exclusion_zone,
             full_matrix: Some(matrix),
         }
     }
@@ -113,8 +196,18 @@ fn profile_from_matrix(x: &Array2<f32>) -> (Vec<f32>, Vec<usize>) {
 
     // for row in x.rows() { //ndarray 0.15
     for row in x.genrows() {
-        let i = row.argmin_skipnan().unwrap();
-        profile.push(row[i]);
+        // Equal distances keep the lowest index
+        let (d, i) = row
+            .iter()
+            .enumerate()
+            .fold((f32::INFINITY, NO_NEIGHBOUR), |a, (j, &d)| {
+                if d < a.0 {
+                    (d, j)
+                } else {
+                    a
+                }
+            });
+        profile.push(d);
         idxs.push(i);
     }
     (profile, idxs)
@@ -178,4 +271,69 @@ mod tests {
             b.get_profile_idxs().as_slice()
         );
     }
+
+    #[test]
+    fn test_ties() {
+        // Repetitions of a pattern are at distance zero, the first one outside the exclusion
+        // zone is the neighbour
+        let pattern = random_data(10, 34);
+        let x = (0..60).map(|i| pattern[i % 10]).collect::<Vec<_>>();
+        let expected = (0..55)
+            .map(|i| if i < 10 { i + 10 } else { i % 10 })
+            .collect::<Vec<_>>();
+        let a = NaiveMatrixProfile::calculate(x.clone(), 6);
+        assert!(a.get_profile().iter().all(|&o| o == 0.0));
+        assert_eq!(a.get_profile_idxs(), &expected);
+        let b = NaiveMatrixProfile::calculate_full_matrix(x, 6);
+        assert_eq!(b.get_profile_idxs(), &expected);
+    }
+
+    #[test]
+    fn test_constant_subsequences() {
+        // Constant subsequences are at distance zero from each other and `sqrt(m)` from the
+        // rest, as in stumpy
+        let mut x = random_data(40, 34);
+        x[5..15].iter_mut().for_each(|o| *o = 3.0);
+        x[25..35].iter_mut().for_each(|o| *o = -1.0);
+        let res = NaiveMatrixProfile::calculate_full_matrix(x, 4);
+        assert_eq!((res.get_profile()[5], res.get_profile_idxs()[5]), (0.0, 7));
+        assert_eq!(
+            (res.get_profile()[31], res.get_profile_idxs()[31]),
+            (0.0, 5)
+        );
+        let matrix = res.get_full_matrix().unwrap();
+        assert_eq!(matrix[[5, 20]], 2.0);
+        assert_eq!(matrix[[20, 31]], 2.0);
+    }
+
+    #[test]
+    fn test_naive_cancellation() {
+        let x = random_data(60, 34);
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        let mut last = 0;
+        let res = NaiveMatrixProfile::calculate_with_progress(
+            x.clone(),
+            4,
+            &mut |done, _| {
+                last = done;
+                if done == 20 {
+                    cancel.cancel();
+                }
+            },
+            &token,
+        );
+        assert_eq!(last, 20);
+        let expected = NaiveMatrixProfile::calculate(x, 4);
+        match res {
+            Outcome::Cancelled { partial, rows_done } => {
+                assert_eq!(rows_done, 20);
+                assert_eq!(partial.get_profile()[..20], expected.get_profile()[..20]);
+                assert!(partial.get_profile_idxs()[20..]
+                    .iter()
+                    .all(|&o| o == NO_NEIGHBOUR));
+            }
+            Outcome::Completed(_) => panic!("The computation was not cancelled"),
+        }
+    }
 }
@@ -0,0 +1,479 @@
+//! Reading and writing NumPy `.npy` and `.npz` files, to move series and profiles between Python
+//! and Rust.
+//!
+//! Only little endian `f32`, `f64` and `i64` arrays in C order are supported.
+//! [`StumpyProfile`] saves profiles with the same arrays as the results of the Python `stumpy`
+//! library. The profiles also follow its convention for constant subsequences, see
+//! [`crate::utils::constant_squared_distance`].
+use crate::matrix_profile::{MatrixProfile, NO_NEIGHBOUR};
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, Write},
+    path::Path,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Values of a NumPy array.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NpyData {
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    I64(Vec<i64>),
+}
+
+impl NpyData {
+    pub fn len(&self) -> usize {
+        match self {
+            NpyData::F32(v) => v.len(),
+            NpyData::F64(v) => v.len(),
+            NpyData::I64(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn descr(&self) -> &'static str {
+        match self {
+            NpyData::F32(_) => "<f4",
+            NpyData::F64(_) => "<f8",
+            NpyData::I64(_) => "<i8",
+        }
+    }
+}
+
+/// Array of a `.npy` file, with its values in row major order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: NpyData,
+}
+
+impl NpyArray {
+    /// Array with the given shape, which must match the number of values.
+    pub fn new(shape: Vec<usize>, data: NpyData) -> Self {
+        assert_eq!(
+            shape.iter().product::<usize>(),
+            data.len(),
+            "The shape doesn't match the number of values"
+        );
+        Self { shape, data }
+    }
+
+    /// One dimensional array.
+    pub fn from_vec(data: NpyData) -> Self {
+        Self::new(vec![data.len()], data)
+    }
+
+    /// Values as `f32`, e.g. to compute the profile of a series. Integers are converted as with
+    /// `as` casts.
+    pub fn to_f32(&self) -> Vec<f32> {
+        match &self.data {
+            NpyData::F32(v) => v.clone(),
+            NpyData::F64(v) => v.iter().map(|&o| o as f32).collect(),
+            NpyData::I64(v) => v.iter().map(|&o| o as f32).collect(),
+        }
+    }
+
+    /// Values as `i64`, floats are truncated.
+    pub fn to_i64(&self) -> Vec<i64> {
+        match &self.data {
+            NpyData::F32(v) => v.iter().map(|&o| o as i64).collect(),
+            NpyData::F64(v) => v.iter().map(|&o| o as i64).collect(),
+            NpyData::I64(v) => v.clone(),
+        }
+    }
+
+    /// Writes the array in the `.npy` format (version 1.0, or 2.0 for very long headers).
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let shape = match self.shape.len() {
+            1 => format!("({},)", self.shape[0]),
+            _ => format!(
+                "({})",
+                self.shape
+                    .iter()
+                    .map(|o| o.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            self.data.descr(),
+            shape
+        );
+        // The data starts aligned to 64 bytes, the header ends with a newline
+        let (version, prefix_len) = if header.len() + 11 <= u16::MAX as usize {
+            (1, 10)
+        } else {
+            (2, 12)
+        };
+        let padding = 63 - (prefix_len + header.len()) % 64;
+        header.extend(std::iter::repeat_n(' ', padding));
+        header.push('\n');
+
+        w.write_all(MAGIC)?;
+        w.write_all(&[version, 0])?;
+        if version == 1 {
+            w.write_all(&(header.len() as u16).to_le_bytes())?;
+        } else {
+            w.write_all(&(header.len() as u32).to_le_bytes())?;
+        }
+        w.write_all(header.as_bytes())?;
+        match &self.data {
+            NpyData::F32(v) => v.iter().try_for_each(|o| w.write_all(&o.to_le_bytes())),
+            NpyData::F64(v) => v.iter().try_for_each(|o| w.write_all(&o.to_le_bytes())),
+            NpyData::I64(v) => v.iter().try_for_each(|o| w.write_all(&o.to_le_bytes())),
+        }
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        Self::read_limited(r, None)
+    }
+
+    /// Reads an array from a stream of at most `limit` bytes, so a corrupt header is rejected
+    /// before allocating more than the available data.
+    fn read_limited<R: Read>(r: &mut R, limit: Option<u64>) -> io::Result<Self> {
+        let mut prefix = [0u8; 8];
+        r.read_exact(&mut prefix)?;
+        if &prefix[..6] != MAGIC {
+            return Err(invalid_data("Not a .npy file"));
+        }
+        let header_len = match prefix[6] {
+            1 => {
+                let mut buf = [0u8; 2];
+                r.read_exact(&mut buf)?;
+                u16::from_le_bytes(buf) as usize
+            }
+            2 | 3 => {
+                let mut buf = [0u8; 4];
+                r.read_exact(&mut buf)?;
+                u32::from_le_bytes(buf) as usize
+            }
+            v => return Err(invalid_data(format!("Unsupported .npy version {}", v))),
+        };
+        let header = read_bytes(r, header_len)?;
+        let header = String::from_utf8_lossy(&header);
+
+        let descr = header_value(&header, "descr")?;
+        let descr = descr.trim_matches(|c| c == '\'' || c == '"');
+        let fortran_order = header_value(&header, "fortran_order")?;
+        let shape = header_value(&header, "shape")?
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .map(|o| o.trim())
+            .filter(|o| !o.is_empty())
+            .map(|o| o.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid_data("Invalid .npy shape"))?;
+        if fortran_order == "True" && shape.len() > 1 {
+            return Err(invalid_data("Fortran ordered arrays are not supported"));
+        }
+
+        let n = shape
+            .iter()
+            .try_fold(1usize, |a, &b| a.checked_mul(b))
+            .ok_or_else(|| invalid_data("The .npy shape is too large"))?;
+        let data = match descr {
+            "<f4" | "=f4" => NpyData::F32(read_values(r, n, limit, f32::from_le_bytes)?),
+            "<f8" | "=f8" => NpyData::F64(read_values(r, n, limit, f64::from_le_bytes)?),
+            "<i8" | "=i8" => NpyData::I64(read_values(r, n, limit, i64::from_le_bytes)?),
+            _ => {
+                return Err(invalid_data(format!(
+                    "Unsupported .npy type '{}', only little endian f4, f8 and i8 are supported",
+                    descr
+                )))
+            }
+        };
+        Ok(Self { shape, data })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Self::read_limited(&mut BufReader::new(file), Some(len))
+    }
+}
+
+/// Value of a key of the header dictionary, e.g. `'<f4'` for `descr`.
+fn header_value<'a>(header: &'a str, key: &str) -> io::Result<&'a str> {
+    let missing = || invalid_data(format!("The .npy header has no '{}'", key));
+    let start = header
+        .find(&format!("'{}'", key))
+        .or_else(|| header.find(&format!("\"{}\"", key)))
+        .ok_or_else(missing)?;
+    let rest = header[start + key.len() + 2..].trim_start();
+    let rest = rest.strip_prefix(':').ok_or_else(missing)?.trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|o| o + 1)
+    } else {
+        rest.find([',', '}'])
+    }
+    .ok_or_else(missing)?;
+    Ok(rest[..end].trim())
+}
+
+/// Reads `n` values, failing when they need more than `limit` bytes.
+fn read_values<R: Read, T, const N: usize>(
+    r: &mut R,
+    n: usize,
+    limit: Option<u64>,
+    from_le_bytes: fn([u8; N]) -> T,
+) -> io::Result<Vec<T>> {
+    let len = n
+        .checked_mul(N)
+        .filter(|&len| limit.is_none_or(|limit| len as u64 <= limit))
+        .ok_or_else(|| invalid_data("The .npy shape is larger than the data"))?;
+    let buf = read_bytes(r, len)?;
+    Ok(buf
+        .chunks_exact(N)
+        .map(|o| from_le_bytes(o.try_into().unwrap()))
+        .collect())
+}
+
+/// Reads exactly `len` bytes, growing the buffer as the data arrives instead of allocating `len`
+/// bytes upfront.
+fn read_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    r.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "The .npy data is truncated",
+        ));
+    }
+    Ok(buf)
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Reads all the arrays of a `.npz` file (as written by `numpy.savez` or
+/// `numpy.savez_compressed`), in the order they are stored with their names.
+pub fn read_npz<P: AsRef<Path>>(path: P) -> io::Result<Vec<(String, NpyArray)>> {
+    read_npz_from(BufReader::new(File::open(path)?))
+}
+
+fn read_npz_from<R: Read + Seek>(r: R) -> io::Result<Vec<(String, NpyArray)>> {
+    let mut archive = ZipArchive::new(r)?;
+    (0..archive.len())
+        .map(|i| {
+            let mut file = archive.by_index(i)?;
+            let name = file.name()?;
+            let name = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+            let size = file.size();
+            Ok((name, NpyArray::read_limited(&mut file, Some(size))?))
+        })
+        .collect()
+}
+
+/// Writes the arrays as a `.npz` file without compression, like `numpy.savez`.
+pub fn write_npz<P: AsRef<Path>>(path: P, arrays: &[(&str, &NpyArray)]) -> io::Result<()> {
+    write_npz_to(BufWriter::new(File::create(path)?), arrays)
+}
+
+fn write_npz_to<W: Write + Seek>(w: W, arrays: &[(&str, &NpyArray)]) -> io::Result<()> {
+    let mut zip = ZipWriter::new(w);
+    for (name, array) in arrays.iter() {
+        let size = array.data.len() as u64 * 8;
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .large_file(size >= u32::MAX as u64);
+        zip.start_file(format!("{}.npy", name), options)?;
+        array.write_to(&mut zip)?;
+    }
+    zip.finish()?.flush()
+}
+
+/// Matrix profile with the arrays of the results of `stumpy.stump`: `P_` with the profile
+/// (`float64`), and `I_`, `left_I_` and `right_I_` with the indices (`int64`, `-1` for missing
+/// neighbours). The left and right indices are only written when available.
+///
+/// With `stumpy` the same arrays can be saved with:
+///
+/// ```python
+/// mp = stumpy.stump(x, m)
+/// np.savez("profile.npz", P_=mp.P_, I_=mp.I_, left_I_=mp.left_I_, right_I_=mp.right_I_)
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct StumpyProfile {
+    pub profile: Vec<f32>,
+    pub profile_idxs: Vec<usize>,
+    pub left_idxs: Option<Vec<usize>>,
+    pub right_idxs: Option<Vec<usize>>,
+}
+
+impl StumpyProfile {
+    pub fn from_profile<T: MatrixProfile>(mp: &T) -> Self {
+        Self {
+            profile: mp.get_profile().clone(),
+            profile_idxs: mp.get_profile_idxs().clone(),
+            left_idxs: None,
+            right_idxs: None,
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let to_array = |idxs: &[usize]| {
+            NpyArray::from_vec(NpyData::I64(
+                idxs.iter()
+                    .map(|&o| if o == NO_NEIGHBOUR { -1 } else { o as i64 })
+                    .collect(),
+            ))
+        };
+        let profile = NpyArray::from_vec(NpyData::F64(
+            self.profile.iter().map(|&o| o as f64).collect(),
+        ));
+        let idxs = [
+            ("I_", Some(&self.profile_idxs)),
+            ("left_I_", self.left_idxs.as_ref()),
+            ("right_I_", self.right_idxs.as_ref()),
+        ]
+        .iter()
+        .filter_map(|(name, idxs)| idxs.map(|o| (*name, to_array(o))))
+        .collect::<Vec<_>>();
+        let mut arrays = vec![("P_", &profile)];
+        arrays.extend(idxs.iter().map(|(name, o)| (*name, o)));
+        write_npz(path, &arrays)
+    }
+
+    /// Reads the arrays saved by [`StumpyProfile::save`] or from `stumpy`, `P_` and `I_` are
+    /// required.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut arrays = read_npz(path)?;
+        let mut take = |name: &str| {
+            arrays
+                .iter()
+                .position(|(o, _)| o == name)
+                .map(|i| arrays.remove(i).1)
+        };
+        let to_idxs = |array: NpyArray| {
+            array
+                .to_i64()
+                .into_iter()
+                .map(|o| if o < 0 { NO_NEIGHBOUR } else { o as usize })
+                .collect::<Vec<_>>()
+        };
+        let profile = take("P_").ok_or_else(|| invalid_data("The .npz file has no 'P_'"))?;
+        let profile_idxs = take("I_").ok_or_else(|| invalid_data("The .npz file has no 'I_'"))?;
+        Ok(Self {
+            profile: profile.to_f32(),
+            profile_idxs: to_idxs(profile_idxs),
+            left_idxs: take("left_I_").map(to_idxs),
+            right_idxs: take("right_I_").map(to_idxs),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::random_data, StompMatrixProfile};
+    use std::{env::temp_dir, io::Cursor};
+
+    #[test]
+    fn test_npy_format() {
+        // Same bytes as `np.save(f, np.array([1.0, 2.0], dtype="<f4"))`
+        let mut expected = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+        expected.extend_from_slice(b"{'descr': '<f4', 'fortran_order': False, 'shape': (2,), }");
+        expected.extend(std::iter::repeat_n(b' ', 128 - expected.len() - 1));
+        expected.push(b'\n');
+        expected.extend_from_slice(&1f32.to_le_bytes());
+        expected.extend_from_slice(&2f32.to_le_bytes());
+
+        let array = NpyArray::from_vec(NpyData::F32(vec![1.0, 2.0]));
+        let mut out = Vec::new();
+        array.write_to(&mut out).unwrap();
+        assert_eq!(out, expected);
+        assert_eq!(NpyArray::read_from(&mut out.as_slice()).unwrap(), array);
+
+        for array in [
+            NpyArray::new(vec![2, 3], NpyData::F64(vec![0.5, 1.0, 1.5, 2.0, 2.5, 3.0])),
+            NpyArray::from_vec(NpyData::I64(vec![-1, 3, 7])),
+            NpyArray::new(vec![], NpyData::F64(vec![4.0])),
+        ]
+        .iter()
+        {
+            let mut out = Vec::new();
+            array.write_to(&mut out).unwrap();
+            assert_eq!(out.len() % 64, array.data.len() * 8 % 64);
+            assert_eq!(&NpyArray::read_from(&mut out.as_slice()).unwrap(), array);
+        }
+
+        let header = "{'descr': '>f8', 'fortran_order': False, 'shape': (1,), }\n";
+        let mut big_endian = b"\x93NUMPY\x01\x00".to_vec();
+        big_endian.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        big_endian.extend_from_slice(header.as_bytes());
+        big_endian.extend_from_slice(&1f64.to_be_bytes());
+        assert!(NpyArray::read_from(&mut big_endian.as_slice()).is_err());
+
+        // Shapes overflowing or larger than the data are rejected without allocating them
+        for shape in ["(4611686018427387904, 4)", "(1000000000000,)"].iter() {
+            let header = format!(
+                "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}\n",
+                shape
+            );
+            let mut corrupt = b"\x93NUMPY\x01\x00".to_vec();
+            corrupt.extend_from_slice(&(header.len() as u16).to_le_bytes());
+            corrupt.extend_from_slice(header.as_bytes());
+            corrupt.extend_from_slice(&1f32.to_le_bytes());
+            let err = NpyArray::read_from(&mut corrupt.as_slice()).unwrap_err();
+            assert_ne!(err.kind(), io::ErrorKind::Other);
+            let err = NpyArray::read_limited(&mut corrupt.as_slice(), Some(corrupt.len() as u64))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_npz() {
+        let a = NpyArray::from_vec(NpyData::F32(random_data(50, 34)));
+        let b = NpyArray::new(vec![2, 2], NpyData::I64(vec![1, 2, 3, 4]));
+        let mut buf = Cursor::new(Vec::new());
+        write_npz_to(&mut buf, &[("x", &a), ("idxs", &b)]).unwrap();
+        buf.set_position(0);
+        let res = read_npz_from(buf).unwrap();
+        assert_eq!(res, vec![("x".to_string(), a), ("idxs".to_string(), b)]);
+    }
+
+    #[test]
+    fn test_stumpy_profile() {
+        let x = NpyArray::from_vec(NpyData::F64(
+            random_data(80, 34).iter().map(|&o| o as f64).collect(),
+        ));
+        let path = temp_dir().join("matrix_profile_test_series.npy");
+        x.save(&path).unwrap();
+        let mut series = NpyArray::load(&path).unwrap().to_f32();
+        std::fs::remove_file(&path).unwrap();
+        series[40] = f32::NAN;
+
+        let mp = StompMatrixProfile::calculate(series, 8);
+        let mut res = StumpyProfile::from_profile(&mp);
+        res.right_idxs = Some(res.profile_idxs.clone());
+        let path = temp_dir().join("matrix_profile_test_profile.npz");
+        res.save(&path).unwrap();
+
+        let arrays = read_npz(&path).unwrap();
+        let names = arrays.iter().map(|(o, _)| o.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["P_", "I_", "right_I_"]);
+        assert!(matches!(arrays[0].1.data, NpyData::F64(_)));
+        assert_eq!(arrays[1].1.to_i64()[40], -1);
+
+        let loaded = StumpyProfile::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, res);
+        assert!(loaded.profile[40].is_infinite());
+        assert_eq!(loaded.profile_idxs[40], NO_NEIGHBOUR);
+    }
+}
@@ -0,0 +1,64 @@
+//! Hooks to follow and cancel long running computations.
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Receives the progress of a computation, any `FnMut(rows_done, total_rows)` closure works.
+pub trait ProgressObserver {
+    /// Called after each row of the distance matrix is processed.
+    fn on_progress(&mut self, rows_done: usize, total_rows: usize);
+}
+
+impl<F: FnMut(usize, usize)> ProgressObserver for F {
+    fn on_progress(&mut self, rows_done: usize, total_rows: usize) {
+        self(rows_done, total_rows)
+    }
+}
+
+/// Flag to cancel a computation, possibly from another thread. Clones share the same flag.
+///
+/// The flag is checked between rows, so the computation stops after the current row.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Result of a computation that can be cancelled.
+#[derive(Debug)]
+pub enum Outcome<T> {
+    Completed(T),
+    /// The computation stopped after `rows_done` rows, `partial` only accounts for those rows.
+    Cancelled {
+        partial: T,
+        rows_done: usize,
+    },
+}
+
+impl<T> Outcome<T> {
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Outcome::Cancelled { .. })
+    }
+
+    /// The result, partial if the computation was cancelled.
+    pub fn into_inner(self) -> T {
+        match self {
+            Outcome::Completed(o) => o,
+            Outcome::Cancelled { partial, .. } => partial,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Semantic segmentation of time series with the FLUSS algorithm, check [here] for more details.
+//!
+//! Subsequences usually have their nearest neighbour in the same regime, so few nearest neighbour
+//! "arcs" cross the boundary between two regimes.
+//!
+//! [here]: https://www.cs.ucr.edu/~eamonn/Segmentation_ICDM.pdf
+use crate::matrix_profile::NO_NEIGHBOUR;
+
+/// Number of windows at the edges of the series where no regime change is reported.
+const EDGE_WINDOWS: usize = 5;
+
+/// Computes the corrected arc curve: the number of nearest neighbour arcs crossing each point,
+/// divided by the number expected if neighbours were random. Values near zero suggest a regime
+/// change, the edges of the series are set to 1.
+///
+/// # Arguments
+///
+/// * `profile_idxs` - Matrix profile indices
+/// * `m` - Window size
+pub fn corrected_arc_curve(profile_idxs: &[usize], m: usize) -> Vec<f32> {
+    let n = profile_idxs.len();
+    let mut marks = vec![0i64; n + 1];
+    for (i, &j) in profile_idxs.iter().enumerate() {
+        if j != NO_NEIGHBOUR {
+            marks[i.min(j)] += 1;
+            marks[i.max(j)] -= 1;
+        }
+    }
+    let edge = EDGE_WINDOWS * m;
+    let mut arcs = 0i64;
+    (0..n)
+        .map(|i| {
+            arcs += marks[i];
+            let expected = 2.0 * i as f64 * (n - i) as f64 / n as f64;
+            if i < edge || i + edge >= n || expected == 0.0 {
+                1.0
+            } else {
+                (arcs as f64 / expected).min(1.0) as f32
+            }
+        })
+        .collect()
+}
+
+/// Finds the `k` most likely regime changes, the lowest points of the corrected arc curve at
+/// least `5 * m` apart, sorted by increasing value.
+///
+/// # Arguments
+///
+/// * `cac` - Corrected arc curve, see [`corrected_arc_curve`]
+/// * `m` - Window size
+/// * `k` - Number of regime changes to find
+pub fn regime_changes(cac: &[f32], m: usize, k: usize) -> Vec<usize> {
+    let mut order = (0..cac.len()).filter(|&i| cac[i] < 1.0).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| cac[a].partial_cmp(&cac[b]).unwrap());
+    let mut changes: Vec<usize> = Vec::with_capacity(k);
+    for i in order {
+        if changes.len() == k {
+            break;
+        }
+        if changes.iter().all(|o| o.abs_diff(i) >= EDGE_WINDOWS * m) {
+            changes.push(i);
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{datasets::Dataset, matrix_profile::MatrixProfile, stomp::StompMatrixProfile};
+
+    #[test]
+    fn test_regime_change() {
+        let data = Dataset::regime_changes(1000, 30, 2, 34);
+        let m = data.m;
+        let mp = StompMatrixProfile::calculate(data.values, m);
+        let cac = corrected_arc_curve(mp.get_profile_idxs(), m);
+        assert_eq!(cac.len(), mp.get_profile().len());
+        assert!(cac.iter().all(|&o| (0.0..=1.0).contains(&o)));
+        let changes = regime_changes(&cac, m, 1);
+        assert!(
+            changes[0].abs_diff(data.regime_changes[0]) < m,
+            "regime change at {}",
+            changes[0]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Serialization helpers for the result types, enabled with the `serde` feature.
+//!
+//! Human readable formats (e.g. JSON) can't represent infinite distances, so they are written as
+//! `null`, and so are the missing neighbours ([`NO_NEIGHBOUR`]). Binary formats keep the raw
+//! values.
+use crate::matrix_profile::NO_NEIGHBOUR;
+use ndarray::prelude::*;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Distances, infinite values are `null` in human readable formats.
+pub(crate) mod distances {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &[f32], s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_seq(
+                v.iter()
+                    .map(|&o| if o.is_finite() { Some(o) } else { None }),
+            )
+        } else {
+            v.serialize(s)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<f32>, D::Error> {
+        if d.is_human_readable() {
+            let v = Vec::<Option<f32>>::deserialize(d)?;
+            Ok(v.into_iter().map(|o| o.unwrap_or(f32::INFINITY)).collect())
+        } else {
+            Vec::deserialize(d)
+        }
+    }
+}
+
+/// Neighbour indices, [`NO_NEIGHBOUR`] is `null` in human readable formats.
+pub(crate) mod indices {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &[usize], s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_seq(
+                v.iter()
+                    .map(|&o| if o == NO_NEIGHBOUR { None } else { Some(o) }),
+            )
+        } else {
+            v.serialize(s)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<usize>, D::Error> {
+        if d.is_human_readable() {
+            let v = Vec::<Option<usize>>::deserialize(d)?;
+            Ok(v.into_iter().map(|o| o.unwrap_or(NO_NEIGHBOUR)).collect())
+        } else {
+            Vec::deserialize(d)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DistanceMatrix {
+    shape: (usize, usize),
+    #[serde(with = "distances")]
+    data: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexMatrix {
+    shape: (usize, usize),
+    #[serde(with = "indices")]
+    data: Vec<usize>,
+}
+
+/// Matrix of distances, written as its shape and row major data.
+pub(crate) mod distance_matrix {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &Array2<f32>, s: S) -> Result<S::Ok, S::Error> {
+        DistanceMatrix {
+            shape: v.dim(),
+            data: v.iter().cloned().collect(),
+        }
+        .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Array2<f32>, D::Error> {
+        let v = DistanceMatrix::deserialize(d)?;
+        Array2::from_shape_vec(v.shape, v.data).map_err(D::Error::custom)
+    }
+}
+
+pub(crate) mod optional_distance_matrix {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &Option<Array2<f32>>, s: S) -> Result<S::Ok, S::Error> {
+        v.as_ref()
+            .map(|v| DistanceMatrix {
+                shape: v.dim(),
+                data: v.iter().cloned().collect(),
+            })
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Array2<f32>>, D::Error> {
+        Option::<DistanceMatrix>::deserialize(d)?
+            .map(|v| Array2::from_shape_vec(v.shape, v.data).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// Matrix of neighbour indices, written as its shape and row major data.
+pub(crate) mod index_matrix {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &Array2<usize>, s: S) -> Result<S::Ok, S::Error> {
+        IndexMatrix {
+            shape: v.dim(),
+            data: v.iter().cloned().collect(),
+        }
+        .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Array2<usize>, D::Error> {
+        let v = IndexMatrix::deserialize(d)?;
+        Array2::from_shape_vec(v.shape, v.data).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::random_data, tiles::calculate_tiled, DampDiscords, MatrixProfile,
+        NaiveMatrixProfile, StompMatrixProfile, TopKMatrixProfile, NO_NEIGHBOUR,
+    };
+    use serde::{de::DeserializeOwned, Serialize};
+
+    fn series() -> Vec<f32> {
+        let mut x = random_data(80, 34);
+        x[40] = f32::NAN;
+        x
+    }
+
+    /// Round trips through JSON and bincode, returning both results.
+    fn round_trip<T: Serialize + DeserializeOwned>(v: &T) -> (T, T) {
+        let json = serde_json::to_string(v).unwrap();
+        let binary = bincode::serialize(v).unwrap();
+        (
+            serde_json::from_str(&json).unwrap(),
+            bincode::deserialize(&binary).unwrap(),
+        )
+    }
+
+    fn assert_same_profile<T: MatrixProfile>(a: &T, b: &T) {
+        assert_eq!(a.get_profile(), b.get_profile());
+        assert_eq!(a.get_profile_idxs(), b.get_profile_idxs());
+        assert_eq!(a.get_window_size(), b.get_window_size());
+        assert_eq!(a.get_exclusion_zone(), b.get_exclusion_zone());
+    }
+
+    #[test]
+    fn test_stomp_round_trip() {
+        let mp = StompMatrixProfile::calculate(series(), 8);
+        assert!(mp.get_profile_idxs().contains(&NO_NEIGHBOUR));
+        let json = serde_json::to_value(&mp).unwrap();
+        assert_eq!(json["m"], 8);
+        assert_eq!(json["exclusion_zone"], 2);
+        assert!(json["profile"][40].is_null());
+        assert!(json["profile_idxs"][40].is_null());
+
+        let (a, b) = round_trip(&mp);
+        assert_same_profile(&a, &mp);
+        assert_same_profile(&b, &mp);
+    }
+
+    #[test]
+    fn test_naive_round_trip() {
+        let mp = NaiveMatrixProfile::calculate_full_matrix(series(), 8);
+        let (a, b) = round_trip(&mp);
+        for res in [a, b].iter() {
+            assert_same_profile(res, &mp);
+            assert_eq!(res.get_full_matrix(), mp.get_full_matrix());
+        }
+    }
+
+    #[test]
+    fn test_other_results_round_trip() {
+        let topk = TopKMatrixProfile::calculate(series(), 8, 3);
+        let (a, b) = round_trip(&topk);
+        for res in [a, b].iter() {
+            assert_eq!(res.get_profiles(), topk.get_profiles());
+            assert_eq!(res.get_profiles_idxs(), topk.get_profiles_idxs());
+            assert_eq!(res.get_exclusion_zone(), topk.get_exclusion_zone());
+        }
+
+        let damp = DampDiscords::calculate(random_data(200, 34), 8, 50, 2);
+        let (a, b) = round_trip(&damp);
+        for res in [a, b].iter() {
+            assert_eq!(res.get_discords(), damp.get_discords());
+            assert_eq!(res.get_discord_distances(), damp.get_discord_distances());
+        }
+
+        let partial = calculate_tiled(&series(), 8, 30);
+        let (a, b) = round_trip(&partial);
+        assert_eq!(a, partial);
+        assert_eq!(b, partial);
+    }
+}
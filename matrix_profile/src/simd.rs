@@ -0,0 +1,406 @@
+//! Vectorised kernels for the distance profile and profile update loops of the STOMP algorithm.
+//!
+//! The explicitly vectorised kernels are only compiled with the `simd` feature, picking AVX-512
+//! or AVX2 at runtime. Every kernel performs the same floating point operations in the same
+//! order, so all of them give bit-identical results.
+
+/// Implementation used for the inner loops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    Avx2,
+    Avx512,
+}
+
+impl Kernel {
+    /// Fastest kernel supported by the running CPU.
+    pub fn detect() -> Self {
+        [Self::Avx512, Self::Avx2]
+            .iter()
+            .cloned()
+            .find(|o| o.is_available())
+            .unwrap_or(Self::Scalar)
+    }
+
+    /// Checks if the kernel is compiled in and supported by the running CPU.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            Self::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            Self::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+            _ => false,
+        }
+    }
+
+    /// Computes the squared distances between the query and every subsequence from their
+    /// sliding dot products (with compensation terms) and statistics, clipping values below
+    /// `f32::EPSILON` to zero.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn distance_profile(
+        &self,
+        distances: &mut [f32],
+        qt: &[f32],
+        qt_comp: &[f32],
+        mean_t: &[f32],
+        sigma_t: &[f32],
+        m: f32,
+        q_mean: f32,
+        q_std: f32,
+    ) {
+        let n = distances.len();
+        assert!(qt.len() == n && qt_comp.len() == n && mean_t.len() == n && sigma_t.len() == n);
+        match self {
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            // Safety: the kernel is only used when available, see `StompWorkspace::with_kernel`
+            Self::Avx2 => unsafe {
+                x86::distance_profile_avx2(
+                    distances, qt, qt_comp, mean_t, sigma_t, m, q_mean, q_std,
+                )
+            },
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            Self::Avx512 => unsafe {
+                x86::distance_profile_avx512(
+                    distances, qt, qt_comp, mean_t, sigma_t, m, q_mean, q_std,
+                )
+            },
+            _ => distance_profile_scalar(distances, qt, qt_comp, mean_t, sigma_t, m, q_mean, q_std),
+        }
+    }
+
+    /// Updates the profile with the distances of the row `idx`, keeping the first (lowest) row
+    /// on ties.
+    pub(crate) fn update_profile(
+        &self,
+        profile: &mut [f32],
+        profile_idxs: &mut [usize],
+        distances: &[f32],
+        idx: usize,
+    ) {
+        let n = profile.len();
+        assert!(profile_idxs.len() == n && distances.len() == n);
+        match self {
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            Self::Avx2 => unsafe {
+                x86::update_profile_avx2(profile, profile_idxs, distances, idx)
+            },
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            Self::Avx512 => unsafe {
+                x86::update_profile_avx512(profile, profile_idxs, distances, idx)
+            },
+            _ => update_profile_scalar(profile, profile_idxs, distances, idx),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn distance_profile_scalar(
+    distances: &mut [f32],
+    qt: &[f32],
+    qt_comp: &[f32],
+    mean_t: &[f32],
+    sigma_t: &[f32],
+    m: f32,
+    q_mean: f32,
+    q_std: f32,
+) {
+    for ((((d, t), c), mt), st) in distances
+        .iter_mut()
+        .zip(qt)
+        .zip(qt_comp)
+        .zip(mean_t)
+        .zip(sigma_t)
+    {
+        let o = 2.0 * (m - ((t + c) - m * mt * q_mean) / (q_std * st));
+        *d = if o < f32::EPSILON { 0f32 } else { o };
+    }
+}
+
+fn update_profile_scalar(
+    profile: &mut [f32],
+    profile_idxs: &mut [usize],
+    distances: &[f32],
+    idx: usize,
+) {
+    for ((profile_d, profile_idx), &d) in profile.iter_mut().zip(profile_idxs).zip(distances) {
+        if *profile_d > d {
+            *profile_idx = idx;
+            *profile_d = d;
+        }
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    #[allow(clippy::too_many_arguments)]
+    pub(super) unsafe fn distance_profile_avx2(
+        distances: &mut [f32],
+        qt: &[f32],
+        qt_comp: &[f32],
+        mean_t: &[f32],
+        sigma_t: &[f32],
+        m: f32,
+        q_mean: f32,
+        q_std: f32,
+    ) {
+        const LANES: usize = 8;
+        let n = distances.len();
+        let chunks = n / LANES * LANES;
+        let (m_v, q_mean_v, q_std_v) = (
+            _mm256_set1_ps(m),
+            _mm256_set1_ps(q_mean),
+            _mm256_set1_ps(q_std),
+        );
+        let (two, eps, zero) = (
+            _mm256_set1_ps(2.0),
+            _mm256_set1_ps(f32::EPSILON),
+            _mm256_setzero_ps(),
+        );
+        for i in (0..chunks).step_by(LANES) {
+            let t = _mm256_loadu_ps(qt.as_ptr().add(i));
+            let c = _mm256_loadu_ps(qt_comp.as_ptr().add(i));
+            let mt = _mm256_loadu_ps(mean_t.as_ptr().add(i));
+            let st = _mm256_loadu_ps(sigma_t.as_ptr().add(i));
+            let num = _mm256_sub_ps(
+                _mm256_add_ps(t, c),
+                _mm256_mul_ps(_mm256_mul_ps(m_v, mt), q_mean_v),
+            );
+            let o = _mm256_mul_ps(
+                two,
+                _mm256_sub_ps(m_v, _mm256_div_ps(num, _mm256_mul_ps(q_std_v, st))),
+            );
+            let below = _mm256_cmp_ps::<_CMP_LT_OQ>(o, eps);
+            _mm256_storeu_ps(
+                distances.as_mut_ptr().add(i),
+                _mm256_blendv_ps(o, zero, below),
+            );
+        }
+        super::distance_profile_scalar(
+            &mut distances[chunks..],
+            &qt[chunks..],
+            &qt_comp[chunks..],
+            &mean_t[chunks..],
+            &sigma_t[chunks..],
+            m,
+            q_mean,
+            q_std,
+        );
+    }
+
+    #[target_feature(enable = "avx512f")]
+    #[allow(clippy::too_many_arguments)]
+    pub(super) unsafe fn distance_profile_avx512(
+        distances: &mut [f32],
+        qt: &[f32],
+        qt_comp: &[f32],
+        mean_t: &[f32],
+        sigma_t: &[f32],
+        m: f32,
+        q_mean: f32,
+        q_std: f32,
+    ) {
+        const LANES: usize = 16;
+        let n = distances.len();
+        let chunks = n / LANES * LANES;
+        let (m_v, q_mean_v, q_std_v) = (
+            _mm512_set1_ps(m),
+            _mm512_set1_ps(q_mean),
+            _mm512_set1_ps(q_std),
+        );
+        let (two, eps, zero) = (
+            _mm512_set1_ps(2.0),
+            _mm512_set1_ps(f32::EPSILON),
+            _mm512_setzero_ps(),
+        );
+        for i in (0..chunks).step_by(LANES) {
+            let t = _mm512_loadu_ps(qt.as_ptr().add(i));
+            let c = _mm512_loadu_ps(qt_comp.as_ptr().add(i));
+            let mt = _mm512_loadu_ps(mean_t.as_ptr().add(i));
+            let st = _mm512_loadu_ps(sigma_t.as_ptr().add(i));
+            let num = _mm512_sub_ps(
+                _mm512_add_ps(t, c),
+                _mm512_mul_ps(_mm512_mul_ps(m_v, mt), q_mean_v),
+            );
+            let o = _mm512_mul_ps(
+                two,
+                _mm512_sub_ps(m_v, _mm512_div_ps(num, _mm512_mul_ps(q_std_v, st))),
+            );
+            let below = _mm512_cmp_ps_mask::<_CMP_LT_OQ>(o, eps);
+            _mm512_storeu_ps(
+                distances.as_mut_ptr().add(i),
+                _mm512_mask_blend_ps(below, o, zero),
+            );
+        }
+        super::distance_profile_scalar(
+            &mut distances[chunks..],
+            &qt[chunks..],
+            &qt_comp[chunks..],
+            &mean_t[chunks..],
+            &sigma_t[chunks..],
+            m,
+            q_mean,
+            q_std,
+        );
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn update_profile_avx2(
+        profile: &mut [f32],
+        profile_idxs: &mut [usize],
+        distances: &[f32],
+        idx: usize,
+    ) {
+        const LANES: usize = 8;
+        let n = profile.len();
+        let chunks = n / LANES * LANES;
+        for i in (0..chunks).step_by(LANES) {
+            let p = _mm256_loadu_ps(profile.as_ptr().add(i));
+            let d = _mm256_loadu_ps(distances.as_ptr().add(i));
+            // Updates are rare after the first rows, so they are applied lane by lane
+            let mut mask = _mm256_movemask_ps(_mm256_cmp_ps::<_CMP_GT_OQ>(p, d)) as u32;
+            if mask != 0 {
+                _mm256_storeu_ps(profile.as_mut_ptr().add(i), _mm256_min_ps(d, p));
+                while mask != 0 {
+                    let lane = mask.trailing_zeros() as usize;
+                    profile_idxs[i + lane] = idx;
+                    mask &= mask - 1;
+                }
+            }
+        }
+        super::update_profile_scalar(
+            &mut profile[chunks..],
+            &mut profile_idxs[chunks..],
+            &distances[chunks..],
+            idx,
+        );
+    }
+
+    #[target_feature(enable = "avx512f")]
+    pub(super) unsafe fn update_profile_avx512(
+        profile: &mut [f32],
+        profile_idxs: &mut [usize],
+        distances: &[f32],
+        idx: usize,
+    ) {
+        const LANES: usize = 16;
+        let n = profile.len();
+        let chunks = n / LANES * LANES;
+        for i in (0..chunks).step_by(LANES) {
+            let p = _mm512_loadu_ps(profile.as_ptr().add(i));
+            let d = _mm512_loadu_ps(distances.as_ptr().add(i));
+            // Updates are rare after the first rows, so they are applied lane by lane
+            let mut mask = _mm512_cmp_ps_mask::<_CMP_GT_OQ>(p, d) as u32;
+            if mask != 0 {
+                _mm512_storeu_ps(
+                    profile.as_mut_ptr().add(i),
+                    _mm512_mask_blend_ps(mask as u16, p, d),
+                );
+                while mask != 0 {
+                    let lane = mask.trailing_zeros() as usize;
+                    profile_idxs[i + lane] = idx;
+                    mask &= mask - 1;
+                }
+            }
+        }
+        super::update_profile_scalar(
+            &mut profile[chunks..],
+            &mut profile_idxs[chunks..],
+            &distances[chunks..],
+            idx,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::random_data;
+
+    #[test]
+    fn test_kernels_match_scalar() {
+        let n = 103;
+        let qt = random_data(n, 34)
+            .iter()
+            .map(|o| o * 40.0)
+            .collect::<Vec<_>>();
+        let qt_comp = random_data(n, 35)
+            .iter()
+            .map(|o| o * 1e-4)
+            .collect::<Vec<_>>();
+        let mean_t = random_data(n, 36);
+        let mut sigma_t = random_data(n, 37);
+        sigma_t[5] = 0.0;
+        sigma_t[70] = f32::NAN;
+
+        let mut expected = vec![0.0; n];
+        distance_profile_scalar(
+            &mut expected,
+            &qt,
+            &qt_comp,
+            &mean_t,
+            &sigma_t,
+            10.0,
+            50.0,
+            20.0,
+        );
+        let mut expected_profile = vec![f32::INFINITY; n];
+        let mut expected_idxs = vec![0; n];
+        update_profile_scalar(&mut expected_profile, &mut expected_idxs, &expected, 3);
+        update_profile_scalar(&mut expected_profile, &mut expected_idxs, &mean_t, 4);
+
+        for kernel in [Kernel::Scalar, Kernel::Avx2, Kernel::Avx512].iter() {
+            if !kernel.is_available() {
+                continue;
+            }
+            let mut distances = vec![0.0; n];
+            kernel.distance_profile(
+                &mut distances,
+                &qt,
+                &qt_comp,
+                &mean_t,
+                &sigma_t,
+                10.0,
+                50.0,
+                20.0,
+            );
+            let same = distances
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| a.to_bits() == b.to_bits());
+            assert!(same, "{:?} distances don't match", kernel);
+
+            let mut profile = vec![f32::INFINITY; n];
+            let mut idxs = vec![0; n];
+            kernel.update_profile(&mut profile, &mut idxs, &distances, 3);
+            kernel.update_profile(&mut profile, &mut idxs, &mean_t, 4);
+            let same = profile
+                .iter()
+                .zip(&expected_profile)
+                .all(|(a, b)| a.to_bits() == b.to_bits());
+            assert!(same, "{:?} profile doesn't match", kernel);
+            assert_eq!(idxs, expected_idxs, "{:?}", kernel);
+        }
+    }
+
+    #[test]
+    fn test_ties_keep_lowest_row() {
+        let distances = random_data(37, 34);
+        for kernel in [Kernel::Scalar, Kernel::Avx2, Kernel::Avx512].iter() {
+            if !kernel.is_available() {
+                continue;
+            }
+            let mut profile = vec![f32::INFINITY; 37];
+            let mut idxs = vec![0; 37];
+            for idx in 3..6 {
+                kernel.update_profile(&mut profile, &mut idxs, &distances, idx);
+            }
+            assert_eq!(profile, distances, "{:?}", kernel);
+            assert!(idxs.iter().all(|&o| o == 3), "{:?}", kernel);
+        }
+    }
+}
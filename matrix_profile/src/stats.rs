@@ -0,0 +1,206 @@
+//! Numerically stable statistics of the subsequences of a time series.
+//!
+//! Computing the variance as `E[x²] - E[x]²` from cumulative sums catastrophically cancels for
+//! series with a large offset, so the series are centered and the rolling statistics are updated
+//! with Welford's algorithm in double precision.
+use ndarray::prelude::*;
+
+/// Removes the mean of the time series, ignoring missing (NaN or infinite) values.
+///
+/// z-normalized distances don't change when shifting the series, so the algorithms using dot
+/// products (STOMP, MASS, DAMP) work on the centered data.
+pub fn remove_mean(x: &Array1<f32>) -> Array1<f32> {
+    let (sum, count) = x
+        .iter()
+        .filter(|o| o.is_finite())
+        .fold((0f64, 0usize), |(sum, count), &o| {
+            (sum + o as f64, count + 1)
+        });
+    if count == 0 {
+        return x.clone();
+    }
+    let mean = sum / count as f64;
+    x.mapv(|o| (o as f64 - mean) as f32)
+}
+
+/// Computes the mean and standard deviation of every subsequence of length `m`.
+pub fn precompute_stats(x: &Array1<f32>, m: usize) -> (Array1<f32>, Array1<f32>) {
+    let n = x.len() - m + 1;
+    let mut means = Array1::zeros(n);
+    let mut stds = Array1::zeros(n);
+
+    // First window using two passes
+    let mut mean = x.iter().take(m).map(|&o| o as f64).sum::<f64>() / m as f64;
+    let mut m2 = x
+        .iter()
+        .take(m)
+        .map(|&o| (o as f64 - mean).powi(2))
+        .sum::<f64>();
+    means[0] = mean as f32;
+    stds[0] = (m2.max(0.0) / m as f64).sqrt() as f32;
+
+    // Rolling Welford updates, replacing `x_out` with `x_in`
+    for i in 1..n {
+        let x_out = x[i - 1] as f64;
+        let x_in = x[i + m - 1] as f64;
+        let prev_mean = mean;
+        mean += (x_in - x_out) / m as f64;
+        m2 += (x_in - x_out) * (x_in - mean + x_out - prev_mean);
+        means[i] = mean as f32;
+        stds[i] = (m2.max(0.0) / m as f64).sqrt() as f32;
+    }
+
+    (means, stds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix_profile::MatrixProfile, naive::NaiveMatrixProfile, stomp::StompMatrixProfile,
+        test_utils::random_data,
+    };
+    use approx::assert_relative_eq;
+
+    /// Brute force statistics in double precision.
+    fn expected_stats(x: &[f32], m: usize) -> (Vec<f32>, Vec<f32>) {
+        x.windows(m)
+            .map(|w| {
+                let mean = w.iter().map(|&o| o as f64).sum::<f64>() / m as f64;
+                let var = w.iter().map(|&o| (o as f64 - mean).powi(2)).sum::<f64>() / m as f64;
+                (mean as f32, var.sqrt() as f32)
+            })
+            .unzip()
+    }
+
+    #[test]
+    fn test_precompute_stats() {
+        let x = random_data(20, 34);
+        let x = Array1::from(x);
+        let (means, stds) = precompute_stats(&x, 4);
+        let means = means.to_vec();
+        let stds = stds.to_vec();
+        let expected_means: Vec<f32> = vec![
+            46.65674847,
+            27.05939168,
+            22.90565668,
+            23.72789943,
+            47.41447021,
+            72.287772,
+            78.788712,
+            86.0191985,
+            64.37987475,
+            39.9538552,
+            22.79070727,
+            13.96505727,
+            15.68540265,
+            18.4393017,
+            34.72254713,
+            26.67163048,
+            29.2358316,
+        ];
+        let expected_stds: Vec<f32> = vec![
+            29.85902311,
+            28.10328597,
+            22.64409521,
+            23.32055262,
+            33.74810079,
+            25.47926001,
+            22.05673344,
+            11.7627809,
+            33.65364617,
+            34.56023576,
+            28.98052781,
+            13.81962595,
+            13.5129983,
+            11.41253782,
+            23.77679109,
+            26.74428495,
+            26.06496464,
+        ];
+        assert_relative_eq!(means.as_slice(), expected_means.as_slice(), epsilon = 1e-4);
+        assert_relative_eq!(stds.as_slice(), expected_stds.as_slice(), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_stats_offset() {
+        let m = 10;
+        for &offset in [1e3f32, 1e5, 1e6].iter() {
+            let x = random_data(2000, 34)
+                .into_iter()
+                .map(|o| o * 1e-2 + offset)
+                .collect::<Vec<_>>();
+            let (means, stds) = precompute_stats(&Array1::from(x.clone()), m);
+            let (means, stds) = (means.to_vec(), stds.to_vec());
+            let (expected_means, expected_stds) = expected_stats(&x, m);
+            assert_relative_eq!(
+                means.as_slice(),
+                expected_means.as_slice(),
+                max_relative = 1e-6
+            );
+            assert_relative_eq!(
+                stds.as_slice(),
+                expected_stds.as_slice(),
+                max_relative = 1e-3
+            );
+        }
+    }
+
+    #[test]
+    fn test_stats_high_dynamic_range() {
+        let m = 8;
+        // Quiet and loud regions several orders of magnitude apart
+        let x = random_data(600, 34)
+            .into_iter()
+            .enumerate()
+            .map(|(i, o)| {
+                if (i / 100) % 2 == 0 {
+                    o * 1e-3
+                } else {
+                    o * 1e3
+                }
+            })
+            .collect::<Vec<_>>();
+        let (_, stds) = precompute_stats(&Array1::from(x.clone()), m);
+        let stds = stds.to_vec();
+        let (_, expected_stds) = expected_stats(&x, m);
+        assert_relative_eq!(
+            stds.as_slice(),
+            expected_stds.as_slice(),
+            max_relative = 1e-3
+        );
+    }
+
+    #[test]
+    fn test_profile_offset_invariance() {
+        let m = 10;
+        let x = random_data(200, 34);
+        let expected = NaiveMatrixProfile::calculate(x.clone(), m);
+        // Kelvin like offset
+        let shifted = x.iter().map(|o| o * 0.1 + 300.0).collect::<Vec<_>>();
+        for res in [
+            StompMatrixProfile::calculate(shifted.clone(), m)
+                .get_profile()
+                .clone(),
+            NaiveMatrixProfile::calculate(shifted, m)
+                .get_profile()
+                .clone(),
+        ]
+        .iter()
+        {
+            assert_relative_eq!(
+                res.as_slice(),
+                expected.get_profile().as_slice(),
+                epsilon = 1e-2
+            );
+        }
+        // Counter like offset
+        let shifted = x.iter().map(|o| o + 1e5).collect::<Vec<_>>();
+        let res = StompMatrixProfile::calculate(shifted, m);
+        assert_relative_eq!(
+            res.get_profile().as_slice(),
+            expected.get_profile().as_slice(),
+            epsilon = 1e-2
+        );
+    }
+}
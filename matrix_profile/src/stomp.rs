@@ -1,152 +1,651 @@
 //! Implementation of the STOMP algorithm to calculate the Matrix Profile, check [here] for more details.
 //!
 //! [here]: https://www.cs.ucr.edu/~eamonn/MatrixProfile.html
-use crate::matrix_profile::MatrixProfile;
-use ndarray::{concatenate, prelude::*};
-use rustfft::{num_complex::Complex32, FftPlanner};
+use crate::{
+    mass::MassPlanner,
+    matrix_profile::{MatrixProfile, NO_NEIGHBOUR},
+    progress::{CancellationToken, Outcome, ProgressObserver},
+    simd::Kernel,
+    stats::{precompute_stats, remove_mean},
+    utils::{
+        constant_squared_distance, constant_subsequences, fill_missing, read_f32s, read_usize,
+        valid_subsequences, write_f32s, write_usize,
+    },
+};
+use ndarray::prelude::*;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StompMatrixProfile {
     /// Matrix profile.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::distances"))]
     profile: Vec<f32>,
     /// Matrix profile indices.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::indices"))]
     profile_idxs: Vec<usize>,
-    #[allow(dead_code)]
     /// Window size.
     m: usize,
+    /// Nearby subsequences excluded as neighbours, at most this far apart.
+    exclusion_zone: usize,
+    /// Maximum drift of the `qt` recurrence observed, see [`DriftControl::measure_every`].
+    max_drift: Option<f32>,
 }
 
-fn precompute_stats(x: &Array1<f32>, m: usize) -> (Array1<f32>, Array1<f32>) {
-    let n = x.len();
-    let mut cumsum = x.clone();
-    cumsum.accumulate_axis_inplace(Axis(0), |&prev, curr| *curr += prev);
-    let mut cumsum2 = x.map(|o| o.powi(2));
-    cumsum2.accumulate_axis_inplace(Axis(0), |&prev, curr| *curr += prev);
-    let sum_t = cumsum.slice(s![(m - 1)..]).to_owned()
-        - concatenate![Axis(0), Array1::zeros(1), cumsum.slice(s![..(n - m)])];
-    let sum_t2 = cumsum2.slice(s![(m - 1)..]).to_owned()
-        - concatenate![Axis(0), Array1::zeros(1), cumsum2.slice(s![..(n - m)])];
-    let mean_t = sum_t / (m as f32);
-    let mean_t2 = sum_t2 / (m as f32);
-    let mean_tp2 = mean_t.map(|o| o.powi(2));
-    let sigma_t2 = mean_t2 - mean_tp2;
-    let sigma_t = sigma_t2.mapv(f32::sqrt);
-    (mean_t, sigma_t)
+/// Options to control the numerical drift accumulated by the `qt` recurrence over many rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DriftControl {
+    /// Recomputes the `qt` row exactly using [`crate::mass::sliding_dot_product`] every `refresh_every` rows.
+    pub refresh_every: Option<usize>,
+    /// Uses compensated (Neumaier) summation in the `qt` recurrence.
+    pub compensated: bool,
+    /// Measures the absolute difference between the `qt` row and the brute-force dot products
+    /// every `measure_every` rows (before any refresh).
+    pub measure_every: Option<usize>,
 }
 
-fn sliding_dot_product(x: &Array1<f32>, m: usize) -> Vec<f32> {
-    let q = x.slice(s![..m]).to_owned();
-    let n = x.len();
-    let m = q.len();
-
-    // Append x with n zeros
-    let ta = concatenate![Axis(0), x.to_owned(), Array1::zeros(n)];
-
-    // Reverse Q
-    let qr = q.slice(s![..;-1]);
-
-    // Append qra
-    let qra = concatenate![Axis(0), qr, Array1::zeros(2 * n - m)];
-
-    // Compute FFTs
-    let mut planner = FftPlanner::<f32>::new();
-    let fft = planner.plan_fft_forward(2 * n);
-
-    let mut qraf = qra
-        .into_raw_vec()
-        .into_iter()
-        .map(|o| Complex32::new(o, 0f32))
-        .collect::<Vec<_>>();
-    fft.process(&mut qraf);
-
-    let mut taf = ta
-        .into_raw_vec()
-        .into_iter()
-        .map(|o| Complex32::new(o, 0f32))
-        .collect::<Vec<_>>();
-    fft.process(&mut taf);
-
-    // Compute the inverse FFT to the element-wise multiplication of qraf and taf
-    let ifft = planner.plan_fft_inverse(2 * n);
-    let mut qt = qraf
-        .into_iter()
-        .zip(taf.into_iter())
-        .map(|(a, b)| a * b)
-        .collect::<Vec<_>>();
-    ifft.process(&mut qt);
-
-    let div = qt.len() as f32;
-    qt.into_iter()
-        .skip(m - 1)
-        .take(n - m + 1)
-        .map(|o| o.re / div)
-        .collect::<Vec<_>>()
+fn sliding_dot_product(planner: &mut MassPlanner, x: &Array1<f32>, m: usize) -> Vec<f32> {
+    planner.sliding_dot_product(x.slice(s![..m]), x.view())
 }
 
-impl MatrixProfile for StompMatrixProfile {
-    fn calculate(x: Vec<f32>, m: usize) -> Self {
-        let x = Array1::from(x);
-        let n = x.len() - m + 1;
+/// Computes the distance profiles of a time series row by row, updating the sliding dot
+/// products with the STOMP recurrence.
+///
+/// The buffers are kept between calls to [`StompRows::reset`], so no allocations happen
+/// between rows (except when refreshing the `qt` row, see [`DriftControl::refresh_every`]).
+pub(crate) struct StompRows {
+    x: Array1<f32>,
+    m: usize,
+    exclusion_zone: usize,
+    valid: Vec<bool>,
+    /// Whether any subsequence touches missing values.
+    has_missing: bool,
+    constant: Vec<bool>,
+    /// Indices of the constant subsequences, their distances are set after the kernel.
+    constant_idxs: Vec<usize>,
+    mean_t: Array1<f32>,
+    sigma_t: Array1<f32>,
+    qt: Vec<f32>,
+    /// Compensation terms of the `qt` row, all zeros unless using compensated summation.
+    qt_comp: Vec<f32>,
+    qt_first: Vec<f32>,
+    distances: Vec<f32>,
+    idx: usize,
+    drift_control: DriftControl,
+    max_drift: Option<f32>,
+    kernel: Kernel,
+    planner: MassPlanner,
+}
+
+impl StompRows {
+    pub(crate) fn new(x: Vec<f32>, m: usize) -> Self {
+        Self::with_drift_control(x, m, DriftControl::default())
+    }
+
+    pub(crate) fn with_drift_control(x: Vec<f32>, m: usize, drift_control: DriftControl) -> Self {
+        let mut rows = Self::empty();
+        rows.reset(&x, m, drift_control);
+        rows
+    }
+
+    /// Rows without any data, the buffers are allocated on the first [`StompRows::reset`].
+    pub(crate) fn empty() -> Self {
+        Self {
+            x: Array1::zeros(0),
+            m: 0,
+            exclusion_zone: 0,
+            valid: Vec::new(),
+            has_missing: false,
+            constant: Vec::new(),
+            constant_idxs: Vec::new(),
+            mean_t: Array1::zeros(0),
+            sigma_t: Array1::zeros(0),
+            qt: Vec::new(),
+            qt_comp: Vec::new(),
+            qt_first: Vec::new(),
+            distances: Vec::new(),
+            idx: 0,
+            drift_control: DriftControl::default(),
+            max_drift: None,
+            kernel: Kernel::detect(),
+            planner: MassPlanner::new(),
+        }
+    }
+
+    /// Prepares the rows of a new time series, reusing the allocated buffers.
+    pub(crate) fn reset(&mut self, x: &[f32], m: usize, drift_control: DriftControl) {
+        let x = Array1::from(x.to_vec());
         // Nearby subsequences are likely highly similar so we define an "exclusion zone" around the diagonal
-        let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+        self.exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+        // Subsequences touching missing values are excluded. The missing values are filled with
+        // zeros so they don't poison the rolling statistics and the `qt` updates
+        self.valid = valid_subsequences(&x, m);
+        self.has_missing = self.valid.iter().any(|&v| !v);
+        self.constant = constant_subsequences(&x, m);
+        self.constant_idxs.clear();
+        let constant = &self.constant;
+        self.constant_idxs
+            .extend((0..constant.len()).filter(|&i| constant[i]));
+        // Distances are shift invariant, removing the mean keeps the products in the `qt`
+        // recurrence small
+        self.x = fill_missing(&remove_mean(&x));
+        self.m = m;
 
-        let (mean_t, sigma_t) = precompute_stats(&x, m);
-        let mut profile: Vec<f32> = vec![f32::INFINITY; n];
-        let mut profile_idxs: Vec<usize> = vec![0; n];
-
-        let mut qt = sliding_dot_product(&x, m);
-        let qt_first = qt.clone();
-        let qt_len = qt.len();
-
-        for idx in 0..n {
-            let q_std = sigma_t[idx].max(f32::EPSILON);
-            if idx > 0 {
-                let qt_ = qt.clone().into_iter().take(qt_len);
-                qt.iter_mut()
-                    .skip(1)
-                    .zip(qt_)
-                    .enumerate()
-                    .for_each(|(i, (a, b))| {
-                        *a = b - (x[i] * x[idx - 1]) + (x[i + m] * x[idx + m - 1])
-                    });
-                qt[0] = qt_first[idx];
+        let (mean_t, sigma_t) = precompute_stats(&self.x, m);
+        self.mean_t = mean_t;
+        self.sigma_t = sigma_t;
+        let qt = sliding_dot_product(&mut self.planner, &self.x, m);
+        let n = qt.len();
+        self.qt_first.clear();
+        self.qt_first.extend_from_slice(&qt);
+        self.qt.clear();
+        self.qt.extend_from_slice(&qt);
+        self.qt_comp.clear();
+        self.qt_comp.resize(n, 0.0);
+        self.distances.clear();
+        self.distances.resize(n, f32::INFINITY);
+
+        self.idx = 0;
+        self.drift_control = drift_control;
+        self.max_drift = None;
+    }
+
+    /// Number of subsequences (rows and columns of the distance matrix).
+    pub(crate) fn len(&self) -> usize {
+        self.qt.len()
+    }
+
+    /// Maximum drift of the `qt` recurrence observed so far.
+    pub(crate) fn max_drift(&self) -> Option<f32> {
+        self.max_drift
+    }
+
+    /// Updates the `qt` row from the previous one, applying the drift control options.
+    fn update_qt(&mut self, idx: usize) {
+        let m = self.m;
+        let x = self.x.as_slice().unwrap();
+        let (x_out, x_in) = (x[idx - 1], x[idx + m - 1]);
+        let qt = self.qt.as_mut_slice();
+        // Updated backwards so the previous row values are still available
+        if self.drift_control.compensated {
+            let qt_comp = self.qt_comp.as_mut_slice();
+            for i in (1..qt.len()).rev() {
+                let delta = (x[i - 1 + m] * x_in) - (x[i - 1] * x_out);
+                let (sum, comp) = neumaier_add(qt[i - 1], qt_comp[i - 1], delta);
+                qt[i] = sum;
+                qt_comp[i] = comp;
+            }
+            qt_comp[0] = 0.0;
+        } else {
+            for i in (1..qt.len()).rev() {
+                qt[i] = qt[i - 1] - (x[i - 1] * x_out) + (x[i - 1 + m] * x_in);
             }
+        }
+        qt[0] = self.qt_first[idx];
 
-            // Calculate distance profile
-            let mut distances = qt
+        let query = self.x.slice(s![idx..(idx + m)]);
+        if matches!(self.drift_control.measure_every, Some(r) if idx.is_multiple_of(r)) {
+            let drift = self
+                .qt
                 .iter()
-                .zip(mean_t.iter())
-                .zip(sigma_t.iter())
-                .map(|((t, mt), st)| {
-                    2.0 * ((m as f32) - (t - (m as f32) * mt * mean_t[idx]) / (q_std * st))
+                .zip(self.qt_comp.iter())
+                .enumerate()
+                .map(|(i, (&t, &c))| {
+                    let exact = query
+                        .iter()
+                        .zip(&x[i..(i + m)])
+                        .map(|(&a, &b)| a as f64 * b as f64)
+                        .sum::<f64>();
+                    (t as f64 + c as f64 - exact).abs() as f32
                 })
-                .map(|o| if o < f32::EPSILON { 0f32 } else { o })
-                .collect::<Vec<_>>();
-
-            // Apply "exclusion zone"
-            let min_idx = idx.saturating_sub(exclusion_zone);
-            let max_idx = (idx + exclusion_zone).min(distances.len());
-            for (i, o) in distances.iter_mut().enumerate() {
-                if (i >= min_idx) && (i <= max_idx) {
-                    *o = f32::INFINITY;
+                .fold(0f32, f32::max);
+            self.max_drift = Some(self.max_drift.unwrap_or(0.0).max(drift));
+        }
+        if matches!(self.drift_control.refresh_every, Some(r) if idx.is_multiple_of(r)) {
+            self.qt = self.planner.sliding_dot_product(query, self.x.view());
+            self.qt_comp.iter_mut().for_each(|o| *o = 0.0);
+        }
+    }
+
+    /// Computes the squared distance profile of the next subsequence, the "exclusion zone" and
+    /// the subsequences touching missing values are set to infinity.
+    ///
+    /// Returns `None` once all the rows are processed.
+    pub(crate) fn next_row(&mut self) -> Option<(usize, &[f32])> {
+        let idx = self.idx;
+        if idx >= self.len() {
+            return None;
+        }
+        self.idx += 1;
+
+        if idx > 0 {
+            self.update_qt(idx);
+        }
+
+        if !self.valid[idx] {
+            self.distances.iter_mut().for_each(|o| *o = f32::INFINITY);
+            return Some((idx, &self.distances));
+        }
+
+        // Calculate distance profile, the z-normalized distance is undefined for constant
+        // subsequences, which are compared with `constant_squared_distance`
+        if self.constant[idx] {
+            for (d, &c) in self.distances.iter_mut().zip(self.constant.iter()) {
+                *d = constant_squared_distance(true, c, self.m).unwrap();
+            }
+        } else {
+            let (mean_t, sigma_t) = (
+                self.mean_t.as_slice().unwrap(),
+                self.sigma_t.as_slice().unwrap(),
+            );
+            self.kernel.distance_profile(
+                &mut self.distances,
+                &self.qt,
+                &self.qt_comp,
+                mean_t,
+                sigma_t,
+                self.m as f32,
+                mean_t[idx],
+                sigma_t[idx].max(f32::EPSILON),
+            );
+            for &j in self.constant_idxs.iter() {
+                self.distances[j] = constant_squared_distance(false, true, self.m).unwrap();
+            }
+        }
+        if self.has_missing {
+            for (d, &v) in self.distances.iter_mut().zip(self.valid.iter()) {
+                if !v {
+                    *d = f32::INFINITY;
                 }
             }
+        }
+
+        // Apply "exclusion zone"
+        let min_idx = idx.saturating_sub(self.exclusion_zone);
+        let max_idx = (idx + self.exclusion_zone).min(self.len() - 1);
+        self.distances[min_idx..=max_idx]
+            .iter_mut()
+            .for_each(|o| *o = f32::INFINITY);
 
+        Some((idx, &self.distances))
+    }
+}
+
+/// Preallocated buffers to compute matrix profiles with the STOMP algorithm, useful to run
+/// repeated profiles (e.g. different windows or series) without allocations between rows.
+pub struct StompWorkspace {
+    rows: StompRows,
+}
+
+impl Default for StompWorkspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StompWorkspace {
+    /// Workspace using the fastest kernel available, see [`Kernel::detect`].
+    pub fn new() -> Self {
+        Self {
+            rows: StompRows::empty(),
+        }
+    }
+
+    /// Workspace using a specific kernel for the inner loops.
+    ///
+    /// # Panics
+    ///
+    /// If the kernel is not available, see [`Kernel::is_available`].
+    pub fn with_kernel(kernel: Kernel) -> Self {
+        assert!(
+            kernel.is_available(),
+            "Kernel {:?} is not available",
+            kernel
+        );
+        let mut rows = StompRows::empty();
+        rows.kernel = kernel;
+        Self { rows }
+    }
+
+    /// Computes the matrix profile using the STOMP algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    pub fn calculate(&mut self, x: &[f32], m: usize) -> StompMatrixProfile {
+        self.calculate_with_drift_control(x, m, DriftControl::default())
+    }
+
+    /// Computes the matrix profile using the STOMP algorithm, controlling the numerical drift of
+    /// the `qt` recurrence.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `drift_control` - Drift control options
+    pub fn calculate_with_drift_control(
+        &mut self,
+        x: &[f32],
+        m: usize,
+        drift_control: DriftControl,
+    ) -> StompMatrixProfile {
+        self.calculate_with_progress(
+            x,
+            m,
+            drift_control,
+            &mut |_, _| {},
+            &CancellationToken::new(),
+        )
+        .into_inner()
+    }
+
+    /// Computes the matrix profile using the STOMP algorithm, reporting the progress after each
+    /// row and stopping early when `token` is cancelled.
+    ///
+    /// A cancelled result only compares each subsequence with the rows processed, so its
+    /// distances are upper bounds of the final ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `drift_control` - Drift control options
+    /// * `observer` - Receives the number of rows processed
+    /// * `token` - Cancellation token, checked between rows
+    pub fn calculate_with_progress(
+        &mut self,
+        x: &[f32],
+        m: usize,
+        drift_control: DriftControl,
+        observer: &mut dyn ProgressObserver,
+        token: &CancellationToken,
+    ) -> Outcome<StompMatrixProfile> {
+        let rows = &mut self.rows;
+        rows.reset(x, m, drift_control);
+        let n = rows.len();
+        let mut profile: Vec<f32> = vec![f32::INFINITY; n];
+        let mut profile_idxs: Vec<usize> = vec![NO_NEIGHBOUR; n];
+
+        let kernel = rows.kernel;
+        loop {
+            if rows.idx < n && token.is_cancelled() {
+                let rows_done = rows.idx;
+                let partial =
+                    StompMatrixProfile::from_squared(profile, profile_idxs, m, rows.max_drift());
+                return Outcome::Cancelled { partial, rows_done };
+            }
+            let (idx, distances) = match rows.next_row() {
+                Some(row) => row,
+                None => break,
+            };
             // Update profile
-            for (i, (profile_d, d)) in profile.iter_mut().zip(distances).enumerate() {
-                if *profile_d > d {
-                    profile_idxs[i] = idx;
-                    *profile_d = d;
-                }
+            kernel.update_profile(&mut profile, &mut profile_idxs, distances, idx);
+            observer.on_progress(idx + 1, n);
+        }
+
+        Outcome::Completed(StompMatrixProfile::from_squared(
+            profile,
+            profile_idxs,
+            m,
+            rows.max_drift(),
+        ))
+    }
+
+    /// Computes the matrix profile using the STOMP algorithm, saving a [`StompCheckpoint`] to
+    /// `path` every `every` rows.
+    ///
+    /// When `path` already holds a checkpoint of the same computation it is resumed from there,
+    /// giving bit-identical results to an uninterrupted run. The checkpoint is removed once the
+    /// computation finishes.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `drift_control` - Drift control options
+    /// * `path` - Checkpoint file
+    /// * `every` - Number of rows between checkpoints
+    pub fn calculate_with_checkpoints<P: AsRef<Path>>(
+        &mut self,
+        x: &[f32],
+        m: usize,
+        drift_control: DriftControl,
+        path: P,
+        every: usize,
+    ) -> io::Result<StompMatrixProfile> {
+        let res = self.calculate_with_checkpoints_until(x, m, drift_control, path, every, None)?;
+        Ok(res.expect("The computation can't stop without a limit"))
+    }
+
+    /// Same as [`StompWorkspace::calculate_with_checkpoints`] but stops after processing row
+    /// `stop_after`, returning `None`, so tests can simulate an interrupted run.
+    pub(crate) fn calculate_with_checkpoints_until<P: AsRef<Path>>(
+        &mut self,
+        x: &[f32],
+        m: usize,
+        drift_control: DriftControl,
+        path: P,
+        every: usize,
+        stop_after: Option<usize>,
+    ) -> io::Result<Option<StompMatrixProfile>> {
+        assert!(every > 0, "Checkpoints must be at least one row apart");
+        let path = path.as_ref();
+        let rows = &mut self.rows;
+        rows.reset(x, m, drift_control);
+        let n = rows.len();
+        let mut profile: Vec<f32> = vec![f32::INFINITY; n];
+        let mut profile_idxs: Vec<usize> = vec![NO_NEIGHBOUR; n];
+
+        let series_hash = hash_series(x);
+        if path.exists() {
+            let checkpoint = StompCheckpoint::load(path)?;
+            if checkpoint.series_len != x.len()
+                || checkpoint.series_hash != series_hash
+                || checkpoint.m != m
+                || checkpoint.drift_control != drift_control
+                || checkpoint.row > n
+                || checkpoint.qt.len() != n
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The checkpoint belongs to a different computation",
+                ));
             }
+            rows.idx = checkpoint.row;
+            rows.qt = checkpoint.qt;
+            rows.qt_comp = checkpoint.qt_comp;
+            rows.max_drift = checkpoint.max_drift;
+            profile = checkpoint.profile;
+            profile_idxs = checkpoint.profile_idxs;
         }
 
-        profile.iter_mut().for_each(|o| *o = o.sqrt());
+        let kernel = rows.kernel;
+        while let Some((idx, distances)) = rows.next_row() {
+            kernel.update_profile(&mut profile, &mut profile_idxs, distances, idx);
+            let row = idx + 1;
+            if row.is_multiple_of(every) && row < n {
+                StompCheckpoint {
+                    series_len: x.len(),
+                    series_hash,
+                    m,
+                    drift_control,
+                    row,
+                    qt: rows.qt.clone(),
+                    qt_comp: rows.qt_comp.clone(),
+                    max_drift: rows.max_drift,
+                    profile: profile.clone(),
+                    profile_idxs: profile_idxs.clone(),
+                }
+                .save(path)?;
+            }
+            if stop_after == Some(idx) {
+                return Ok(None);
+            }
+        }
 
-        StompMatrixProfile {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(Some(StompMatrixProfile::from_squared(
             profile,
             profile_idxs,
             m,
+            rows.max_drift(),
+        )))
+    }
+}
+
+/// Snapshot of a STOMP computation after a number of rows, see
+/// [`StompWorkspace::calculate_with_checkpoints`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct StompCheckpoint {
+    /// Length of the series, used with the hash to check the checkpoint is resumed with the same
+    /// data.
+    series_len: usize,
+    series_hash: u64,
+    /// Window size.
+    m: usize,
+    drift_control: DriftControl,
+    /// Number of rows processed.
+    row: usize,
+    /// `qt` row of the last processed row, with its compensation terms.
+    qt: Vec<f32>,
+    qt_comp: Vec<f32>,
+    max_drift: Option<f32>,
+    /// Squared matrix profile so far.
+    profile: Vec<f32>,
+    profile_idxs: Vec<usize>,
+}
+
+impl StompCheckpoint {
+    const MAGIC: &'static [u8; 8] = b"MPSTOMP1";
+
+    /// Number of rows processed.
+    pub fn get_row(&self) -> usize {
+        self.row
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    /// Writes the checkpoint in a compact little endian binary format.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(Self::MAGIC)?;
+        write_usize(w, self.series_len)?;
+        w.write_all(&self.series_hash.to_le_bytes())?;
+        write_usize(w, self.m)?;
+        let dc = self.drift_control;
+        for &v in [dc.refresh_every, dc.measure_every].iter() {
+            write_usize(w, v.unwrap_or(0))?;
+        }
+        write_usize(w, dc.compensated as usize)?;
+        write_usize(w, self.row)?;
+        write_usize(w, self.qt.len())?;
+        write_f32s(w, &self.qt)?;
+        write_f32s(w, &self.qt_comp)?;
+        write_f32s(w, &[self.max_drift.unwrap_or(f32::NAN)])?;
+        write_f32s(w, &self.profile)?;
+        self.profile_idxs
+            .iter()
+            .try_for_each(|&i| write_usize(w, i))
+    }
+
+    /// Reads a checkpoint written with [`StompCheckpoint::write_to`].
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != Self::MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a STOMP checkpoint",
+            ));
         }
+        let series_len = read_usize(r)?;
+        let mut hash = [0u8; 8];
+        r.read_exact(&mut hash)?;
+        let m = read_usize(r)?;
+        let every = |v: usize| if v == 0 { None } else { Some(v) };
+        let refresh_every = every(read_usize(r)?);
+        let measure_every = every(read_usize(r)?);
+        let compensated = read_usize(r)? != 0;
+        let row = read_usize(r)?;
+        let n = read_usize(r)?;
+        let qt = read_f32s(r, n)?;
+        let qt_comp = read_f32s(r, n)?;
+        let max_drift = read_f32s(r, 1)?[0];
+        let profile = read_f32s(r, n)?;
+        let profile_idxs = (0..n)
+            .map(|_| read_usize(r))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            series_len,
+            series_hash: u64::from_le_bytes(hash),
+            m,
+            drift_control: DriftControl {
+                refresh_every,
+                compensated,
+                measure_every,
+            },
+            row,
+            qt,
+            qt_comp,
+            max_drift: if max_drift.is_nan() {
+                None
+            } else {
+                Some(max_drift)
+            },
+            profile,
+            profile_idxs,
+        })
+    }
+
+    /// Saves the checkpoint, writing to a temporary file first so a crash while saving keeps the
+    /// previous checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        self.write_to(&mut w)?;
+        w.into_inner()?.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// FNV-1a hash of the bits of the series.
+fn hash_series(x: &[f32]) -> u64 {
+    x.iter()
+        .flat_map(|o| o.to_bits().to_le_bytes())
+        .fold(0xcbf29ce484222325, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Adds `value` to `sum` using Neumaier's compensated summation, returning the new sum and
+/// compensation term.
+fn neumaier_add(sum: f32, comp: f32, value: f32) -> (f32, f32) {
+    let t = sum + value;
+    let c = if sum.abs() >= value.abs() {
+        (sum - t) + value
+    } else {
+        (value - t) + sum
+    };
+    (t, comp + c)
+}
+
+impl MatrixProfile for StompMatrixProfile {
+    fn calculate(x: Vec<f32>, m: usize) -> Self {
+        Self::calculate_with_drift_control(x, m, DriftControl::default())
     }
 
     fn get_profile(&self) -> &Vec<f32> {
@@ -156,6 +655,80 @@ impl MatrixProfile for StompMatrixProfile {
     fn get_profile_idxs(&self) -> &Vec<usize> {
         &self.profile_idxs
     }
+
+    fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    fn get_exclusion_zone(&self) -> usize {
+        self.exclusion_zone
+    }
+}
+
+impl StompMatrixProfile {
+    /// Computes the matrix profile using the STOMP algorithm, controlling the numerical drift of
+    /// the `qt` recurrence.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `drift_control` - Drift control options
+    pub fn calculate_with_drift_control(
+        x: Vec<f32>,
+        m: usize,
+        drift_control: DriftControl,
+    ) -> Self {
+        StompWorkspace::new().calculate_with_drift_control(&x, m, drift_control)
+    }
+
+    /// Computes the matrix profile using the STOMP algorithm, reporting the progress after each
+    /// row and stopping early when `token` is cancelled, see
+    /// [`StompWorkspace::calculate_with_progress`].
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `observer` - Receives the number of rows processed
+    /// * `token` - Cancellation token, checked between rows
+    pub fn calculate_with_progress(
+        x: Vec<f32>,
+        m: usize,
+        observer: &mut dyn ProgressObserver,
+        token: &CancellationToken,
+    ) -> Outcome<Self> {
+        StompWorkspace::new().calculate_with_progress(
+            &x,
+            m,
+            DriftControl::default(),
+            observer,
+            token,
+        )
+    }
+
+    /// Builds the result from the squared distances of the profile.
+    fn from_squared(
+        mut profile: Vec<f32>,
+        profile_idxs: Vec<usize>,
+        m: usize,
+        max_drift: Option<f32>,
+    ) -> Self {
+        profile.iter_mut().for_each(|o| *o = o.sqrt());
+        Self {
+            profile,
+            profile_idxs,
+            m,
+            exclusion_zone: (m as f32 / 4f32).ceil() as usize,
+            max_drift,
+        }
+    }
+
+    /// Maximum absolute drift of the `qt` recurrence against the brute-force dot products, only
+    /// available when measured, see [`DriftControl::measure_every`].
+    pub fn get_max_drift(&self) -> Option<f32> {
+        self.max_drift
+    }
 }
 
 #[cfg(test)]
@@ -164,60 +737,11 @@ mod tests {
     use crate::test_utils::random_data;
     use approx::assert_relative_eq;
 
-    #[test]
-    fn test_precompute_stats() {
-        let x = random_data(20, 34);
-        let x = Array1::from(x);
-        let (means, stds) = precompute_stats(&x, 4);
-        let means = means.to_vec();
-        let stds = stds.to_vec();
-        let expected_means: Vec<f32> = vec![
-            46.65674847,
-            27.05939168,
-            22.90565668,
-            23.72789943,
-            47.41447021,
-            72.287772,
-            78.788712,
-            86.0191985,
-            64.37987475,
-            39.9538552,
-            22.79070727,
-            13.96505727,
-            15.68540265,
-            18.4393017,
-            34.72254713,
-            26.67163048,
-            29.2358316,
-        ];
-        let expected_stds: Vec<f32> = vec![
-            29.85902311,
-            28.10328597,
-            22.64409521,
-            23.32055262,
-            33.74810079,
-            25.47926001,
-            22.05673344,
-            11.7627809,
-            33.65364617,
-            34.56023576,
-            28.98052781,
-            13.81962595,
-            13.5129983,
-            11.41253782,
-            23.77679109,
-            26.74428495,
-            26.06496464,
-        ];
-        assert_relative_eq!(means.as_slice(), expected_means.as_slice(), epsilon = 1e-4);
-        assert_relative_eq!(stds.as_slice(), expected_stds.as_slice(), epsilon = 1e-4);
-    }
-
     #[test]
     fn test_sliding_dot_product() {
         let x = random_data(20, 34);
         let x = Array1::from(x);
-        let res = sliding_dot_product(&x, 4);
+        let res = sliding_dot_product(&mut MassPlanner::new(), &x, 4);
         let expected: Vec<f32> = vec![
             12273.65375704,
             7976.24013643,
@@ -299,4 +823,214 @@ mod tests {
             expected.get_profile_idxs().as_slice(),
         );
     }
+
+    #[test]
+    fn test_correlation_profile() {
+        let m = 10;
+        let x = random_data(100, 34);
+        let res = StompMatrixProfile::calculate(x.clone(), m);
+        let x = Array1::from(x);
+        let correlations = res.get_correlation_profile();
+
+        for (i, (&rho, &j)) in correlations
+            .iter()
+            .zip(res.get_profile_idxs().iter())
+            .enumerate()
+        {
+            let a = crate::utils::normalize(x.slice(s![i..(i + m)]).to_owned());
+            let b = crate::utils::normalize(x.slice(s![j..(j + m)]).to_owned());
+            let expected = a.dot(&b) / (m as f32);
+            assert_relative_eq!(rho, expected, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_drift_control() {
+        let m = 50;
+        let x = random_data(3000, 34)
+            .into_iter()
+            .map(|o| o + 1000.0)
+            .collect::<Vec<_>>();
+        let measure = |refresh_every, compensated| {
+            let drift_control = DriftControl {
+                refresh_every,
+                compensated,
+                measure_every: Some(97),
+            };
+            StompMatrixProfile::calculate_with_drift_control(x.clone(), m, drift_control)
+        };
+
+        let plain = measure(None, false);
+        let compensated = measure(None, true);
+        let refreshed = measure(Some(97), false);
+        let plain_drift = plain.get_max_drift().unwrap();
+        let compensated_drift = compensated.get_max_drift().unwrap();
+        let refreshed_drift = refreshed.get_max_drift().unwrap();
+        println!(
+            "drift: plain {}, compensated {}, refreshed {}",
+            plain_drift, compensated_drift, refreshed_drift
+        );
+        assert!(compensated_drift < plain_drift);
+        assert!(refreshed_drift < plain_drift);
+
+        // Default options don't measure and keep the results unchanged
+        let default = StompMatrixProfile::calculate(x, m);
+        assert!(default.get_max_drift().is_none());
+        assert_eq!(default.get_profile(), plain.get_profile());
+
+        let x = random_data(100, 34);
+        let drift_control = DriftControl {
+            refresh_every: Some(10),
+            compensated: true,
+            measure_every: None,
+        };
+        let res = StompMatrixProfile::calculate_with_drift_control(x.clone(), 10, drift_control);
+        let expected = crate::naive::NaiveMatrixProfile::calculate(x, 10);
+        assert_relative_eq!(
+            res.get_profile().as_slice(),
+            expected.get_profile().as_slice(),
+            epsilon = 1e-3
+        );
+        assert_eq!(res.get_profile_idxs(), expected.get_profile_idxs());
+    }
+
+    #[test]
+    fn test_workspace() {
+        let mut workspace = StompWorkspace::new();
+        for &(n, m, seed) in [(100, 10, 34), (60, 4, 35), (200, 20, 36)].iter() {
+            let x = random_data(n, seed);
+            let res = workspace.calculate(&x, m);
+            let expected = crate::naive::NaiveMatrixProfile::calculate(x, m);
+            assert_eq!(res.get_window_size(), m);
+            assert_relative_eq!(
+                res.get_profile().as_slice(),
+                expected.get_profile().as_slice(),
+                epsilon = 1e-3
+            );
+            assert_eq!(res.get_profile_idxs(), expected.get_profile_idxs());
+        }
+        // The buffers of the previous series must not leak into the next profiles
+        let x = random_data(100, 34);
+        let reused = workspace.calculate(&x, 10);
+        let fresh = StompWorkspace::new().calculate(&x, 10);
+        assert_eq!(reused.get_profile(), fresh.get_profile());
+        assert_eq!(reused.get_profile_idxs(), fresh.get_profile_idxs());
+    }
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let x = random_data(300, 34);
+        let m = 10;
+        let drift_control = DriftControl {
+            compensated: true,
+            measure_every: Some(50),
+            ..DriftControl::default()
+        };
+        let path = std::env::temp_dir().join(format!("mp_checkpoint_{}.bin", std::process::id()));
+        let expected =
+            StompMatrixProfile::calculate_with_drift_control(x.clone(), m, drift_control);
+
+        let mut workspace = StompWorkspace::new();
+        let res = workspace
+            .calculate_with_checkpoints_until(&x, m, drift_control, &path, 40, Some(130))
+            .unwrap();
+        assert!(res.is_none());
+        let checkpoint = StompCheckpoint::load(&path).unwrap();
+        assert_eq!(checkpoint.get_row(), 120);
+        let mut bytes = Vec::new();
+        checkpoint.write_to(&mut bytes).unwrap();
+        assert_eq!(
+            StompCheckpoint::read_from(&mut bytes.as_slice()).unwrap(),
+            checkpoint
+        );
+
+        // Resuming with other data fails
+        let mut other = x.clone();
+        other[0] += 1.0;
+        assert!(workspace
+            .calculate_with_checkpoints(&other, m, drift_control, &path, 40)
+            .is_err());
+
+        let res = StompWorkspace::new()
+            .calculate_with_checkpoints(&x, m, drift_control, &path, 40)
+            .unwrap();
+        assert!(!path.exists());
+        assert_eq!(res.get_profile(), expected.get_profile());
+        assert_eq!(res.get_profile_idxs(), expected.get_profile_idxs());
+        assert_eq!(res.get_max_drift(), expected.get_max_drift());
+    }
+
+    #[test]
+    fn test_progress_and_cancellation() {
+        let x = random_data(200, 34);
+        let m = 10;
+        let expected = StompMatrixProfile::calculate(x.clone(), m);
+
+        let mut reported = Vec::new();
+        let res = StompMatrixProfile::calculate_with_progress(
+            x.clone(),
+            m,
+            &mut |done, total| reported.push((done, total)),
+            &CancellationToken::new(),
+        );
+        assert!(!res.is_cancelled());
+        assert_eq!(reported.len(), 191);
+        assert_eq!(reported[0], (1, 191));
+        assert_eq!(reported[190], (191, 191));
+        let res = res.into_inner();
+        assert_eq!(res.get_profile(), expected.get_profile());
+
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        let res = StompMatrixProfile::calculate_with_progress(
+            x,
+            m,
+            &mut |done, _| {
+                if done == 50 {
+                    cancel.cancel();
+                }
+            },
+            &token,
+        );
+        match res {
+            Outcome::Cancelled { partial, rows_done } => {
+                assert_eq!(rows_done, 50);
+                assert!(partial.get_profile_idxs()[..40].iter().all(|&o| o < 50));
+                for (&d, &e) in partial.get_profile().iter().zip(expected.get_profile()) {
+                    assert!(d >= e);
+                }
+            }
+            Outcome::Completed(_) => panic!("The computation was not cancelled"),
+        }
+    }
+
+    #[test]
+    fn test_stomp_missing_values() {
+        let m = 10;
+        let mut x = random_data(100, 34);
+        x[30] = f32::NAN;
+        x[70] = f32::INFINITY;
+        let res = StompMatrixProfile::calculate(x.clone(), m);
+        let expected = crate::naive::NaiveMatrixProfile::calculate(x, m);
+        let profile = res.get_profile();
+        let idxs = res.get_profile_idxs();
+
+        for (i, (&d, &j)) in profile.iter().zip(idxs.iter()).enumerate() {
+            let touches_gap = (21..=30).contains(&i) || (61..=70).contains(&i);
+            if touches_gap {
+                assert!(d.is_infinite());
+                assert_eq!(j, NO_NEIGHBOUR);
+            } else {
+                assert!(d.is_finite());
+                assert!(!(21..=30).contains(&j) && !(61..=70).contains(&j));
+            }
+        }
+
+        assert_relative_eq!(
+            profile.as_slice(),
+            expected.get_profile().as_slice(),
+            epsilon = 1e-3
+        );
+        assert_eq!(idxs.as_slice(), expected.get_profile_idxs().as_slice());
+    }
 }
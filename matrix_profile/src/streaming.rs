@@ -0,0 +1,280 @@
+//! Incremental matrix profile for streaming data (STAMPI), check [here] for more details.
+//!
+//! Each new value adds a subsequence whose distance profile against the past subsequences is
+//! computed in `O(n)` by updating the sliding dot products of the previous one. The new distances
+//! give the left matrix profile of the new subsequence and update the (right) profile of the past
+//! ones, so the profile after each value is the same as computing it from scratch.
+//!
+//! [here]: https://www.cs.ucr.edu/~eamonn/PID4481997_extend_Matrix%20Profile_I.pdf
+use crate::{
+    matrix_profile::{MatrixProfile, NO_NEIGHBOUR},
+    utils::constant_squared_distance,
+};
+
+pub struct StreamingMatrixProfile {
+    /// Values received so far, shifted by the first finite value and with missing values set to
+    /// zero, so they don't poison the `qt` updates.
+    x: Vec<f64>,
+    /// Whether each value is finite.
+    finite: Vec<bool>,
+    /// Shift applied to the values, keeps the products in the `qt` recurrence small.
+    shift: Option<f64>,
+    /// Missing values in the last `m` values.
+    missing: usize,
+    /// Last value received and the length of the run of equal values ending with it, to flag
+    /// the constant subsequences.
+    last: f32,
+    run: usize,
+    /// Whether each subsequence is free of missing values.
+    valid: Vec<bool>,
+    constant: Vec<bool>,
+    means: Vec<f64>,
+    stds: Vec<f64>,
+    /// Dot products of the last subsequence with all the subsequences.
+    qt: Vec<f64>,
+    profile: Vec<f32>,
+    profile_idxs: Vec<usize>,
+    left_profile: Vec<f32>,
+    left_profile_idxs: Vec<usize>,
+    right_profile: Vec<f32>,
+    right_profile_idxs: Vec<usize>,
+    /// Window size.
+    m: usize,
+    /// Nearby subsequences excluded as neighbours, at most this far apart.
+    exclusion_zone: usize,
+}
+
+impl StreamingMatrixProfile {
+    /// Empty profile, values are added with [`StreamingMatrixProfile::push`].
+    ///
+    /// # Arguments
+    ///
+    /// * `m` - Window size
+    pub fn new(m: usize) -> Self {
+        assert!(m > 1, "The window must have at least two values");
+        Self {
+            x: Vec::new(),
+            finite: Vec::new(),
+            shift: None,
+            missing: 0,
+            last: f32::NAN,
+            run: 0,
+            valid: Vec::new(),
+            constant: Vec::new(),
+            means: Vec::new(),
+            stds: Vec::new(),
+            qt: Vec::new(),
+            profile: Vec::new(),
+            profile_idxs: Vec::new(),
+            left_profile: Vec::new(),
+            left_profile_idxs: Vec::new(),
+            right_profile: Vec::new(),
+            right_profile_idxs: Vec::new(),
+            m,
+            // Nearby subsequences are likely highly similar so we define an "exclusion zone" around the diagonal
+            exclusion_zone: (m as f32 / 4f32).ceil() as usize,
+        }
+    }
+
+    /// Adds a value to the series, updating the profile. Missing values can be represented as
+    /// NaN or infinite.
+    pub fn push(&mut self, value: f32) {
+        let m = self.m;
+        let is_finite = value.is_finite();
+        self.run = if value == self.last { self.run + 1 } else { 1 };
+        self.last = value;
+        if is_finite && self.shift.is_none() {
+            self.shift = Some(value as f64);
+        }
+        let value = if is_finite {
+            value as f64 - self.shift.unwrap()
+        } else {
+            0.0
+        };
+        self.x.push(value);
+        self.finite.push(is_finite);
+        self.missing += !is_finite as usize;
+        let len = self.x.len();
+        if len > m && !self.finite[len - m - 1] {
+            self.missing -= 1;
+        }
+        if len < m {
+            return;
+        }
+
+        // New subsequence
+        let idx = len - m;
+        let x = &self.x;
+        let query = &x[idx..];
+        let mean = query.iter().sum::<f64>() / m as f64;
+        let std = (query.iter().map(|o| (o - mean).powi(2)).sum::<f64>() / m as f64).sqrt();
+        self.valid.push(self.missing == 0);
+        self.constant.push(self.run >= m);
+        self.means.push(mean);
+        self.stds.push(std);
+
+        // Sliding dot products from the previous ones, updated backwards so the previous values
+        // are still available
+        self.qt.push(0.0);
+        if idx > 0 {
+            let (x_out, x_in) = (x[idx - 1], x[len - 1]);
+            for j in (1..idx).rev() {
+                self.qt[j] = self.qt[j - 1] - x[j - 1] * x_out + x[j + m - 1] * x_in;
+            }
+            self.qt[0] = query.iter().zip(&x[..m]).map(|(a, b)| a * b).sum();
+        }
+        self.qt[idx] = query.iter().map(|o| o * o).sum();
+
+        self.profile.push(f32::INFINITY);
+        self.profile_idxs.push(NO_NEIGHBOUR);
+        self.left_profile.push(f32::INFINITY);
+        self.left_profile_idxs.push(NO_NEIGHBOUR);
+        self.right_profile.push(f32::INFINITY);
+        self.right_profile_idxs.push(NO_NEIGHBOUR);
+        if !self.valid[idx] || idx <= self.exclusion_zone {
+            return;
+        }
+
+        // Past subsequences outside the "exclusion zone", the new one is their right neighbour
+        let q_std = std.max(f32::EPSILON as f64);
+        for j in 0..(idx - self.exclusion_zone) {
+            if !self.valid[j] {
+                continue;
+            }
+            let d = match constant_squared_distance(self.constant[j], self.constant[idx], m) {
+                Some(d) => d.sqrt(),
+                None => {
+                    let rho = (self.qt[j] - m as f64 * self.means[j] * mean)
+                        / (m as f64 * self.stds[j].max(f32::EPSILON as f64) * q_std);
+                    (2.0 * m as f64 * (1.0 - rho)).max(0.0).sqrt() as f32
+                }
+            };
+            // The lowest index is kept on ties, the new subsequence is the highest one
+            if d < self.left_profile[idx] {
+                self.left_profile[idx] = d;
+                self.left_profile_idxs[idx] = j;
+            }
+            if d < self.right_profile[j] {
+                self.right_profile[j] = d;
+                self.right_profile_idxs[j] = idx;
+            }
+            if d < self.profile[j] {
+                self.profile[j] = d;
+                self.profile_idxs[j] = idx;
+            }
+        }
+        self.profile[idx] = self.left_profile[idx];
+        self.profile_idxs[idx] = self.left_profile_idxs[idx];
+    }
+
+    /// Adds several values in order, see [`StreamingMatrixProfile::push`].
+    pub fn extend<I: IntoIterator<Item = f32>>(&mut self, values: I) {
+        values.into_iter().for_each(|o| self.push(o));
+    }
+
+    /// Number of values received.
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Distance of each subsequence to its nearest neighbour before it.
+    pub fn get_left_profile(&self) -> &Vec<f32> {
+        &self.left_profile
+    }
+
+    pub fn get_left_profile_idxs(&self) -> &Vec<usize> {
+        &self.left_profile_idxs
+    }
+
+    /// Distance of each subsequence to its nearest neighbour after it, so far.
+    pub fn get_right_profile(&self) -> &Vec<f32> {
+        &self.right_profile
+    }
+
+    pub fn get_right_profile_idxs(&self) -> &Vec<usize> {
+        &self.right_profile_idxs
+    }
+}
+
+impl MatrixProfile for StreamingMatrixProfile {
+    fn calculate(x: Vec<f32>, m: usize) -> Self {
+        let mut res = Self::new(m);
+        res.extend(x);
+        res
+    }
+
+    fn get_profile(&self) -> &Vec<f32> {
+        &self.profile
+    }
+
+    fn get_profile_idxs(&self) -> &Vec<usize> {
+        &self.profile_idxs
+    }
+
+    fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    fn get_exclusion_zone(&self) -> usize {
+        self.exclusion_zone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{naive::NaiveMatrixProfile, test_utils::random_data};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_streaming() {
+        let mut x = random_data(120, 34);
+        x[70] = f32::NAN;
+        let m = 10;
+        let mut mp = StreamingMatrixProfile::new(m);
+        mp.extend(x[..60].iter().cloned());
+        let expected = NaiveMatrixProfile::calculate(x[..60].to_vec(), m);
+        assert_relative_eq!(
+            mp.get_profile().as_slice(),
+            expected.get_profile().as_slice(),
+            epsilon = 1e-3
+        );
+        assert_eq!(mp.get_profile_idxs(), expected.get_profile_idxs());
+
+        mp.extend(x[60..].iter().cloned());
+        assert_eq!(mp.len(), 120);
+        let expected = NaiveMatrixProfile::calculate_full_matrix(x.clone(), m);
+        assert_relative_eq!(
+            mp.get_profile().as_slice(),
+            expected.get_profile().as_slice(),
+            epsilon = 1e-3
+        );
+        assert_eq!(mp.get_profile_idxs(), expected.get_profile_idxs());
+        assert_eq!(mp.get_profile_idxs()[65], NO_NEIGHBOUR);
+
+        // Left and right profiles are the nearest neighbours before and after each subsequence
+        let matrix = expected.get_full_matrix().unwrap();
+        for (i, row) in matrix.outer_iter().enumerate() {
+            let nearest = |js: &mut dyn Iterator<Item = usize>| {
+                js.filter(|&j| row[j].is_finite())
+                    .fold((f32::INFINITY, NO_NEIGHBOUR), |best, j| {
+                        if row[j] < best.0 {
+                            (row[j], j)
+                        } else {
+                            best
+                        }
+                    })
+            };
+            let (left, left_idx) = nearest(&mut (0..i));
+            let (right, right_idx) = nearest(&mut ((i + 1)..row.len()));
+            assert_relative_eq!(mp.get_left_profile()[i], left, epsilon = 1e-3);
+            assert_relative_eq!(mp.get_right_profile()[i], right, epsilon = 1e-3);
+            assert_eq!(mp.get_left_profile_idxs()[i], left_idx);
+            assert_eq!(mp.get_right_profile_idxs()[i], right_idx);
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! Tiled computation of the Matrix Profile, for series that don't fit in memory.
+//!
+//! The distance matrix is split in rectangular [`Tile`]s, each one only needs the data of its
+//! rows and columns, so tiles can be computed independently (e.g. from a [`MmapSeries`] or in
+//! different processes) and their [`PartialProfile`]s merged in any order.
+//!
+//! The distance matrix is symmetric, so [`Tile::cover`] only returns the tiles on and above the
+//! diagonal and [`compute_tile`] also returns the partial profile of the transposed tile.
+use crate::{
+    mass::MassPlanner,
+    matrix_profile::NO_NEIGHBOUR,
+    stats::{precompute_stats, remove_mean},
+    utils::{
+        constant_squared_distance, constant_subsequences, fill_missing, read_f32s, read_usize,
+        valid_subsequences, write_f32s, write_usize,
+    },
+};
+use ndarray::prelude::*;
+use std::io::{self, Read, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::File, path::Path};
+
+/// Rectangular region of the distance matrix, the ranges are half-open subsequence indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tile {
+    pub row_start: usize,
+    pub row_end: usize,
+    pub col_start: usize,
+    pub col_end: usize,
+}
+
+impl Tile {
+    pub fn new(rows: std::ops::Range<usize>, cols: std::ops::Range<usize>) -> Self {
+        assert!(
+            rows.start < rows.end && cols.start < cols.end,
+            "Tiles can't be empty"
+        );
+        Self {
+            row_start: rows.start,
+            row_end: rows.end,
+            col_start: cols.start,
+            col_end: cols.end,
+        }
+    }
+
+    /// Splits the distance matrix of `n` subsequences in square tiles of `size` subsequences
+    /// (smaller on the borders), keeping only the tiles on and above the diagonal.
+    pub fn cover(n: usize, size: usize) -> Vec<Self> {
+        assert!(size > 0, "The tile size must be positive");
+        let starts = (0..n).step_by(size).collect::<Vec<_>>();
+        starts
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &row)| {
+                starts[i..]
+                    .iter()
+                    .map(move |&col| Self::new(row..(row + size).min(n), col..(col + size).min(n)))
+            })
+            .collect()
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self::new(self.col_start..self.col_end, self.row_start..self.row_end)
+    }
+
+    /// Smallest tile containing both tiles.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            self.row_start.min(other.row_start)..self.row_end.max(other.row_end),
+            self.col_start.min(other.col_start)..self.col_end.max(other.col_end),
+        )
+    }
+
+    /// Whether the tile is on the diagonal, i.e. the tile is its own transpose.
+    pub fn is_diagonal(&self) -> bool {
+        self.row_start == self.col_start && self.row_end == self.col_end
+    }
+}
+
+/// Matrix profile of the columns of a tile: the nearest neighbour of each column among the rows
+/// of the tile.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartialProfile {
+    /// Tile the profile was computed from, the bounding tile after merging.
+    tile: Tile,
+    /// Distances of the columns `tile.col_start..tile.col_end`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::distances"))]
+    profile: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::indices"))]
+    profile_idxs: Vec<usize>,
+    /// Window size.
+    m: usize,
+    exclusion_zone: usize,
+}
+
+impl PartialProfile {
+    /// Partial profile without any neighbours.
+    pub fn empty(tile: Tile, m: usize) -> Self {
+        let n = tile.col_end - tile.col_start;
+        Self {
+            tile,
+            profile: vec![f32::INFINITY; n],
+            profile_idxs: vec![NO_NEIGHBOUR; n],
+            m,
+            exclusion_zone: (m as f32 / 4f32).ceil() as usize,
+        }
+    }
+
+    /// Merges two partial profiles, keeping the nearest neighbour of each column (the lowest
+    /// index on ties). The merge is associative and commutative, so partial results can be
+    /// combined in any order.
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut merged = Self::empty(self.tile.union(&other.tile), self.m);
+        merged.exclusion_zone = self.exclusion_zone;
+        merged.merge_into(self);
+        merged.merge_into(other);
+        merged
+    }
+
+    /// Merges `other` in place, like [`PartialProfile::merge`] but only touching the columns of
+    /// `other`, which must be within the columns of `self`. Used to accumulate many tiles without
+    /// copying the whole profile for each one.
+    pub fn merge_into(&mut self, other: &Self) {
+        assert_eq!(self.m, other.m, "Partial profiles use different windows");
+        assert_eq!(
+            self.exclusion_zone, other.exclusion_zone,
+            "Partial profiles use different exclusion zones"
+        );
+        assert!(
+            self.tile.col_start <= other.tile.col_start && other.tile.col_end <= self.tile.col_end,
+            "The columns of the merged profile must be within the columns of the accumulator"
+        );
+        let offset = other.tile.col_start - self.tile.col_start;
+        let profile = self.profile[offset..].iter_mut();
+        let profile_idxs = self.profile_idxs[offset..].iter_mut();
+        for (((d, i), &other_d), &other_i) in profile
+            .zip(profile_idxs)
+            .zip(&other.profile)
+            .zip(&other.profile_idxs)
+        {
+            if other_d < *d || (other_d == *d && other_i < *i) {
+                *d = other_d;
+                *i = other_i;
+            }
+        }
+        self.tile = self.tile.union(&other.tile);
+    }
+
+    pub fn get_tile(&self) -> Tile {
+        self.tile
+    }
+
+    /// Distances of the columns `get_tile().col_start..get_tile().col_end`.
+    pub fn get_profile(&self) -> &Vec<f32> {
+        &self.profile
+    }
+
+    pub fn get_profile_idxs(&self) -> &Vec<usize> {
+        &self.profile_idxs
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    pub fn get_exclusion_zone(&self) -> usize {
+        self.exclusion_zone
+    }
+
+    /// Writes the partial profile in a compact little endian binary format.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let tile = self.tile;
+        for &v in [tile.row_start, tile.row_end, tile.col_start, tile.col_end].iter() {
+            write_usize(w, v)?;
+        }
+        write_usize(w, self.m)?;
+        write_usize(w, self.exclusion_zone)?;
+        write_f32s(w, &self.profile)?;
+        self.profile_idxs
+            .iter()
+            .try_for_each(|&i| write_usize(w, i))
+    }
+
+    /// Reads a partial profile written with [`PartialProfile::write_to`].
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let (row_start, row_end) = (read_usize(r)?, read_usize(r)?);
+        let (col_start, col_end) = (read_usize(r)?, read_usize(r)?);
+        if row_start >= row_end || col_start >= col_end {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid tile"));
+        }
+        let tile = Tile::new(row_start..row_end, col_start..col_end);
+        let (m, exclusion_zone) = (read_usize(r)?, read_usize(r)?);
+        let n = col_end - col_start;
+        let profile = read_f32s(r, n)?;
+        let profile_idxs = (0..n)
+            .map(|_| read_usize(r))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            tile,
+            profile,
+            profile_idxs,
+            m,
+            exclusion_zone,
+        })
+    }
+}
+
+/// Computes the partial profiles of a tile using the STOMP recurrence over its rows.
+///
+/// Only `x[row_start..row_end + m - 1]` and `x[col_start..col_end + m - 1]` are read, so `x` can
+/// be a memory mapped series. Returns the partial profile of the tile and, for tiles off the
+/// diagonal, the one of the transposed tile.
+///
+/// # Arguments
+///
+/// * `x` - Time series data
+/// * `m` - Window size
+/// * `tile` - Tile of the distance matrix to compute
+pub fn compute_tile(x: &[f32], m: usize, tile: Tile) -> Vec<PartialProfile> {
+    let mut cols_partial = PartialProfile::empty(tile, m);
+    let mut rows_partial = PartialProfile::empty(tile.transpose(), m);
+    tile_distances(x, m, tile, |i, j, d| {
+        // Rows and columns are processed in order, so equal distances keep the lowest index
+        if d < cols_partial.profile[j] {
+            cols_partial.profile[j] = d;
+            cols_partial.profile_idxs[j] = tile.row_start + i;
+        }
+        if d < rows_partial.profile[i] {
+            rows_partial.profile[i] = d;
+            rows_partial.profile_idxs[i] = tile.col_start + j;
+        }
+    });
+
+    let mut partials = vec![cols_partial];
+    if !tile.is_diagonal() {
+        partials.push(rows_partial);
+    }
+    for partial in partials.iter_mut() {
+        partial.profile.iter_mut().for_each(|o| *o = o.sqrt());
+    }
+    partials
+}
+
+/// Calls `f(i, j, d)` with the squared distance `d` of every cell of the tile, row by row, using
+/// the STOMP recurrence. `i` and `j` are relative to the tile, the cells in the "exclusion zone"
+/// or touching missing values are skipped.
+pub(crate) fn tile_distances<F>(x: &[f32], m: usize, tile: Tile, f: F)
+where
+    F: FnMut(usize, usize, f32),
+{
+    assert!(
+        tile.row_end.max(tile.col_end) + m - 1 <= x.len(),
+        "The tile is out of the distance matrix"
+    );
+    let rows = &x[tile.row_start..(tile.row_end + m - 1)];
+    let cols = &x[tile.col_start..(tile.col_end + m - 1)];
+    let exclusion_zone = (m as f32 / 4f32).ceil() as usize;
+    cross_distances(
+        rows,
+        cols,
+        m,
+        Some((tile.row_start, tile.col_start, exclusion_zone)),
+        f,
+    );
+}
+
+/// Calls `f(i, j, d)` with the squared distance `d` between the subsequence `i` of `rows` and
+/// the subsequence `j` of `cols`, row by row, using the STOMP recurrence. Subsequences touching
+/// missing values are skipped, constant ones follow [`constant_squared_distance`].
+///
+/// When both series are parts of the same one, `exclusion` gives the position of `rows` and
+/// `cols` in the series and the "exclusion zone", the cells in the zone are skipped.
+pub(crate) fn cross_distances<F>(
+    rows: &[f32],
+    cols: &[f32],
+    m: usize,
+    exclusion: Option<(usize, usize, usize)>,
+    mut f: F,
+) where
+    F: FnMut(usize, usize, f32),
+{
+    assert!(
+        m > 0 && rows.len() >= m && cols.len() >= m,
+        "The series must be at least as long as the window"
+    );
+    // Distances are invariant to shifting each side, centering them keeps the products in the
+    // `qt` recurrence small even when the series have very different offsets
+    let prepare = |x: &[f32]| {
+        let x = Array1::from(x.to_vec());
+        let valid = valid_subsequences(&x, m);
+        let constant = constant_subsequences(&x, m);
+        let x = fill_missing(&remove_mean(&x));
+        let (means, stds) = precompute_stats(&x, m);
+        (x, valid, constant, means, stds)
+    };
+    let (x_rows, valid_rows, constant_rows, mean_rows, sigma_rows) = prepare(rows);
+    let (x_cols, valid_cols, constant_cols, mean_cols, sigma_cols) = prepare(cols);
+
+    let mut planner = MassPlanner::new();
+    let mut qt = planner.sliding_dot_product(x_rows.slice(s![..m]), x_cols.view());
+    let qt_first = planner.sliding_dot_product(x_cols.slice(s![..m]), x_rows.view());
+
+    let mf = m as f32;
+    for (i, &row_valid) in valid_rows.iter().enumerate() {
+        if i > 0 {
+            let (x_out, x_in) = (x_rows[i - 1], x_rows[i + m - 1]);
+            for j in (1..qt.len()).rev() {
+                qt[j] = qt[j - 1] - (x_cols[j - 1] * x_out) + (x_cols[j - 1 + m] * x_in);
+            }
+            qt[0] = qt_first[i];
+        }
+        if !row_valid {
+            continue;
+        }
+        let (q_mean, q_std) = (mean_rows[i], sigma_rows[i].max(f32::EPSILON));
+        for (j, &t) in qt.iter().enumerate() {
+            if !valid_cols[j] {
+                continue;
+            }
+            if let Some((row_start, col_start, exclusion_zone)) = exclusion {
+                if (row_start + i).abs_diff(col_start + j) <= exclusion_zone {
+                    continue;
+                }
+            }
+            if let Some(d) = constant_squared_distance(constant_rows[i], constant_cols[j], m) {
+                f(i, j, d);
+                continue;
+            }
+            let d = 2.0 * (mf - (t - mf * mean_cols[j] * q_mean) / (q_std * sigma_cols[j]));
+            f(i, j, if d < f32::EPSILON { 0f32 } else { d });
+        }
+    }
+}
+
+/// Computes the full matrix profile tile by tile, the result covers all the subsequences.
+///
+/// # Arguments
+///
+/// * `x` - Time series data
+/// * `m` - Window size
+/// * `tile_size` - Number of subsequences on each side of the tiles
+pub fn calculate_tiled(x: &[f32], m: usize, tile_size: usize) -> PartialProfile {
+    let n = x.len() - m + 1;
+    let mut res = PartialProfile::empty(Tile::new(0..n, 0..n), m);
+    for tile in Tile::cover(n, tile_size) {
+        for partial in compute_tile(x, m, tile) {
+            res.merge_into(&partial);
+        }
+    }
+    res
+}
+
+/// Time series stored on disk as raw native endian `f32` values, memory mapped so only the pages
+/// used by a tile are loaded.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct MmapSeries {
+    mmap: memmap2::Mmap,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MmapSeries {
+    /// Maps the file in memory, the file must not be modified while mapped.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: the file is opened read only, modifying it while mapped is documented as
+        // not allowed
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        if mmap.len() % std::mem::size_of::<f32>() != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The file size is not a multiple of 4 bytes",
+            ));
+        }
+        Ok(Self { mmap })
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        let len = self.mmap.len() / std::mem::size_of::<f32>();
+        if len == 0 {
+            return &[];
+        }
+        // Safety: mappings are page aligned and every bit pattern is a valid `f32`
+        unsafe { std::slice::from_raw_parts(self.mmap.as_ptr() as *const f32, len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix_profile::MatrixProfile, stomp::StompMatrixProfile, test_utils::random_data,
+    };
+    use approx::assert_relative_eq;
+
+    fn assert_matches_stomp(res: &PartialProfile, x: Vec<f32>, m: usize) {
+        let expected = StompMatrixProfile::calculate(x, m);
+        assert_relative_eq!(
+            res.get_profile().as_slice(),
+            expected.get_profile().as_slice(),
+            epsilon = 1e-3
+        );
+        assert_eq!(res.get_profile_idxs(), expected.get_profile_idxs());
+    }
+
+    #[test]
+    fn test_tile_cover() {
+        let tiles = Tile::cover(10, 4);
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.iter().all(|o| o.row_start <= o.col_start));
+        assert_eq!(tiles[5], Tile::new(8..10, 8..10));
+        // Tiles off the diagonal also cover their transpose
+        let cells = tiles
+            .iter()
+            .map(|o| {
+                let area = (o.row_end - o.row_start) * (o.col_end - o.col_start);
+                if o.is_diagonal() {
+                    area
+                } else {
+                    2 * area
+                }
+            })
+            .sum::<usize>();
+        assert_eq!(cells, 100);
+    }
+
+    #[test]
+    fn test_tiled_profile() {
+        let x = random_data(300, 34);
+        for &tile_size in [1000, 64, 37].iter() {
+            let res = calculate_tiled(&x, 10, tile_size);
+            assert_eq!(res.get_tile(), Tile::new(0..291, 0..291));
+            assert_matches_stomp(&res, x.clone(), 10);
+        }
+    }
+
+    #[test]
+    fn test_merge_order() {
+        let x = random_data(200, 34);
+        let partials = Tile::cover(191, 50)
+            .into_iter()
+            .flat_map(|tile| compute_tile(&x, 10, tile))
+            .collect::<Vec<_>>();
+        let forward = partials[1..]
+            .iter()
+            .fold(partials[0].clone(), |a, b| a.merge(b));
+        let backward = partials[..(partials.len() - 1)]
+            .iter()
+            .rev()
+            .fold(partials[partials.len() - 1].clone(), |a, b| b.merge(&a));
+        assert_eq!(forward, backward);
+        let mut in_place = PartialProfile::empty(Tile::new(0..191, 0..191), 10);
+        partials.iter().for_each(|o| in_place.merge_into(o));
+        assert_eq!(in_place, forward);
+        assert_matches_stomp(&forward, x, 10);
+    }
+
+    #[test]
+    fn test_partial_profile_encoding() {
+        let x = random_data(120, 34);
+        let partials = compute_tile(&x, 10, Tile::new(0..40, 40..111));
+        for partial in partials.iter() {
+            let mut bytes = Vec::new();
+            partial.write_to(&mut bytes).unwrap();
+            let decoded = PartialProfile::read_from(&mut bytes.as_slice()).unwrap();
+            assert_eq!(&decoded, partial);
+        }
+        let mut bytes = Vec::new();
+        partials[0].write_to(&mut bytes).unwrap();
+        bytes.pop();
+        assert!(PartialProfile::read_from(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_mmap_series() {
+        let x = random_data(250, 34);
+        let path = std::env::temp_dir().join(format!("mp_tiles_{}.f32", std::process::id()));
+        let bytes = x.iter().flat_map(|o| o.to_ne_bytes()).collect::<Vec<_>>();
+        std::fs::write(&path, bytes).unwrap();
+        let series = MmapSeries::open(&path).unwrap();
+        assert_eq!(series.as_slice(), x.as_slice());
+        let res = calculate_tiled(series.as_slice(), 12, 60);
+        drop(series);
+        std::fs::remove_file(&path).unwrap();
+        assert_matches_stomp(&res, x, 12);
+    }
+}
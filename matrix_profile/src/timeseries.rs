@@ -0,0 +1,382 @@
+//! Time series with timestamps, to check the sampling before computing a profile and to map the
+//! positional indices of the results back to time.
+//!
+//! Timestamps are integers in any unit (e.g. seconds since the Unix epoch), dates can be
+//! converted with [`days_from_civil`] and [`civil_from_days`].
+use crate::{
+    matrix_profile::{MatrixProfile, NO_NEIGHBOUR},
+    motifs::Motif,
+};
+use std::{error::Error, fmt};
+
+/// Problems found when validating the timestamps of a series.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeSeriesError {
+    /// There are not as many timestamps as values.
+    LengthMismatch { timestamps: usize, values: usize },
+    /// The timestamp at `idx` is not after the previous one.
+    NotIncreasing { idx: usize },
+    /// The step between the timestamps at `idx - 1` and `idx` is not the sampling period.
+    IrregularSpacing { idx: usize, step: i64, period: i64 },
+    /// The sampling period is not positive.
+    InvalidPeriod(i64),
+}
+
+impl fmt::Display for TimeSeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSeriesError::LengthMismatch { timestamps, values } => write!(
+                f,
+                "{} timestamps were given for {} values",
+                timestamps, values
+            ),
+            TimeSeriesError::NotIncreasing { idx } => write!(
+                f,
+                "The timestamp at position {} is not after the previous one",
+                idx
+            ),
+            TimeSeriesError::IrregularSpacing { idx, step, period } => write!(
+                f,
+                "The step before position {} is {} but the sampling period is {}",
+                idx, step, period
+            ),
+            TimeSeriesError::InvalidPeriod(period) => {
+                write!(f, "The sampling period must be positive, got {}", period)
+            }
+        }
+    }
+}
+
+impl Error for TimeSeriesError {}
+
+/// How [`TimeSeries::resample`] fills the points of the new grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resampling {
+    /// Linear interpolation between the surrounding values.
+    Linear,
+    /// Last value at or before the point.
+    Previous,
+    /// Only points matching a timestamp keep their value, the rest are missing values (NaN),
+    /// which the profiles skip instead of matching made-up data.
+    Missing,
+}
+
+/// Values with strictly increasing timestamps, and optionally a regular sampling period.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeSeries {
+    timestamps: Vec<i64>,
+    values: Vec<f32>,
+    period: Option<i64>,
+}
+
+impl TimeSeries {
+    /// Series with arbitrary (but strictly increasing) timestamps.
+    pub fn new(timestamps: Vec<i64>, values: Vec<f32>) -> Result<Self, TimeSeriesError> {
+        if timestamps.len() != values.len() {
+            return Err(TimeSeriesError::LengthMismatch {
+                timestamps: timestamps.len(),
+                values: values.len(),
+            });
+        }
+        if let Some(idx) = (1..timestamps.len()).find(|&i| timestamps[i] <= timestamps[i - 1]) {
+            return Err(TimeSeriesError::NotIncreasing { idx });
+        }
+        Ok(Self {
+            timestamps,
+            values,
+            period: None,
+        })
+    }
+
+    /// Series sampled every `period`, checking the timestamps are evenly spaced.
+    pub fn with_period(
+        timestamps: Vec<i64>,
+        values: Vec<f32>,
+        period: i64,
+    ) -> Result<Self, TimeSeriesError> {
+        let mut res = Self::new(timestamps, values)?;
+        res.check_period(period)?;
+        res.period = Some(period);
+        Ok(res)
+    }
+
+    /// Regularly sampled series starting at `start`.
+    pub fn from_regular(start: i64, period: i64, values: Vec<f32>) -> Self {
+        assert!(period > 0, "The sampling period must be positive");
+        let timestamps = (0..values.len() as i64)
+            .map(|i| start + i * period)
+            .collect();
+        Self {
+            timestamps,
+            values,
+            period: Some(period),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get_timestamps(&self) -> &Vec<i64> {
+        &self.timestamps
+    }
+
+    pub fn get_values(&self) -> &Vec<f32> {
+        &self.values
+    }
+
+    /// Sampling period, if given or checked with [`TimeSeries::regular_period`].
+    pub fn get_period(&self) -> Option<i64> {
+        self.period
+    }
+
+    /// Sampling period of an evenly spaced series, taken from its first step.
+    pub fn regular_period(&mut self) -> Result<i64, TimeSeriesError> {
+        if let Some(period) = self.period {
+            return Ok(period);
+        }
+        let period = match self.timestamps.as_slice() {
+            [a, b, ..] => b - a,
+            _ => return Err(TimeSeriesError::InvalidPeriod(0)),
+        };
+        self.check_period(period)?;
+        self.period = Some(period);
+        Ok(period)
+    }
+
+    fn check_period(&self, period: i64) -> Result<(), TimeSeriesError> {
+        if period <= 0 {
+            return Err(TimeSeriesError::InvalidPeriod(period));
+        }
+        let irregular = (1..self.len())
+            .map(|i| (i, self.timestamps[i] - self.timestamps[i - 1]))
+            .find(|&(_, step)| step != period);
+        match irregular {
+            Some((idx, step)) => Err(TimeSeriesError::IrregularSpacing { idx, step, period }),
+            None => Ok(()),
+        }
+    }
+
+    /// Resamples the series every `period` from its first timestamp, so it can be profiled.
+    pub fn resample(&self, period: i64, resampling: Resampling) -> Self {
+        assert!(period > 0, "The sampling period must be positive");
+        let (start, end) = match (self.timestamps.first(), self.timestamps.last()) {
+            (Some(&start), Some(&end)) => (start, end),
+            _ => return Self::from_regular(0, period, Vec::new()),
+        };
+        let n = ((end - start) / period + 1) as usize;
+        let mut values = Vec::with_capacity(n);
+        // Index of the last timestamp at or before the current point
+        let mut j = 0;
+        for i in 0..n {
+            let t = start + i as i64 * period;
+            while j + 1 < self.len() && self.timestamps[j + 1] <= t {
+                j += 1;
+            }
+            let exact = self.timestamps[j] == t;
+            let value = match resampling {
+                _ if exact => self.values[j],
+                Resampling::Previous => self.values[j],
+                Resampling::Missing => f32::NAN,
+                Resampling::Linear => {
+                    let (t0, t1) = (self.timestamps[j], self.timestamps[j + 1]);
+                    let w = (t - t0) as f64 / (t1 - t0) as f64;
+                    (self.values[j] as f64 * (1.0 - w) + self.values[j + 1] as f64 * w) as f32
+                }
+            };
+            values.push(value);
+        }
+        Self::from_regular(start, period, values)
+    }
+
+    /// Computes a matrix profile of the values, which requires a regular sampling (positions
+    /// stand for time in the distances). Irregular series can be resampled first with
+    /// [`TimeSeries::resample`].
+    pub fn matrix_profile<T: MatrixProfile>(&mut self, m: usize) -> Result<T, TimeSeriesError> {
+        self.regular_period()?;
+        Ok(T::calculate(self.values.clone(), m))
+    }
+
+    /// Timestamp where the subsequence at `idx` starts.
+    pub fn timestamp(&self, idx: usize) -> i64 {
+        self.timestamps[idx]
+    }
+
+    /// Timestamps of the first and last values of the subsequence of length `m` at `idx`.
+    pub fn subsequence_span(&self, idx: usize, m: usize) -> (i64, i64) {
+        (self.timestamps[idx], self.timestamps[idx + m - 1])
+    }
+
+    /// Start timestamps of the neighbours of a matrix profile, `None` without neighbour.
+    pub fn neighbour_timestamps(&self, profile_idxs: &[usize]) -> Vec<Option<i64>> {
+        profile_idxs
+            .iter()
+            .map(|&o| {
+                if o == NO_NEIGHBOUR {
+                    None
+                } else {
+                    Some(self.timestamps[o])
+                }
+            })
+            .collect()
+    }
+
+    /// Start timestamps of the subsequences of motifs found with
+    /// [`crate::motifs::top_motifs`].
+    pub fn motif_timestamps(&self, motifs: &[Motif]) -> Vec<TimedMotif> {
+        motifs
+            .iter()
+            .map(|o| TimedMotif {
+                timestamp: self.timestamps[o.idx],
+                neighbour_timestamp: self.timestamps[o.neighbour],
+                distance: o.distance,
+            })
+            .collect()
+    }
+}
+
+/// Motif with the start timestamps of its subsequences.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimedMotif {
+    pub timestamp: i64,
+    pub neighbour_timestamp: i64,
+    pub distance: f32,
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar, e.g. to build the
+/// timestamps of daily series (multiply by 86400 for seconds).
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    assert!(
+        (1..=12).contains(&month) && (1..=31).contains(&day),
+        "Invalid date"
+    );
+    // Years starting in March, so the leap day is the last one
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date `(year, month, day)` of a number of days since 1970-01-01, inverse of
+/// [`days_from_civil`].
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{motifs::top_motifs, StompMatrixProfile};
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(
+            TimeSeries::new(vec![0, 1], vec![1.0]),
+            Err(TimeSeriesError::LengthMismatch {
+                timestamps: 2,
+                values: 1
+            })
+        );
+        assert_eq!(
+            TimeSeries::new(vec![0, 2, 2], vec![1.0; 3]),
+            Err(TimeSeriesError::NotIncreasing { idx: 2 })
+        );
+        assert_eq!(
+            TimeSeries::with_period(vec![0, 2, 4, 7], vec![1.0; 4], 2),
+            Err(TimeSeriesError::IrregularSpacing {
+                idx: 3,
+                step: 3,
+                period: 2
+            })
+        );
+        let mut ts = TimeSeries::new(vec![10, 15, 20], vec![1.0; 3]).unwrap();
+        assert_eq!(ts.get_period(), None);
+        assert_eq!(ts.regular_period(), Ok(5));
+        assert_eq!(ts.get_period(), Some(5));
+    }
+
+    #[test]
+    fn test_resample() {
+        let ts = TimeSeries::new(vec![0, 10, 40, 50], vec![1.0, 2.0, 5.0, 4.0]).unwrap();
+        let linear = ts.resample(10, Resampling::Linear);
+        assert_eq!(linear.get_timestamps(), &vec![0, 10, 20, 30, 40, 50]);
+        assert_eq!(linear.get_values(), &vec![1.0, 2.0, 3.0, 4.0, 5.0, 4.0]);
+        assert_eq!(linear.get_period(), Some(10));
+        let previous = ts.resample(10, Resampling::Previous);
+        assert_eq!(previous.get_values(), &vec![1.0, 2.0, 2.0, 2.0, 5.0, 4.0]);
+        let missing = ts.resample(10, Resampling::Missing);
+        assert!(missing.get_values()[2].is_nan() && missing.get_values()[3].is_nan());
+        assert_eq!(missing.get_values()[4], 5.0);
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        for &days in [-800_000, -1, 0, 11016, 11017, 16436, 800_000].iter() {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(16436), (2015, 1, 1));
+    }
+
+    #[test]
+    fn test_accidents() {
+        let text = include_str!("../tests/data/accident_UK.csv");
+        let (timestamps, values): (Vec<_>, Vec<_>) = text
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let (date, value) = line.split_once(',').unwrap();
+                let parts = date
+                    .split('/')
+                    .map(|o| o.parse::<i64>().unwrap())
+                    .collect::<Vec<_>>();
+                let days = days_from_civil(parts[2], parts[1] as u32, parts[0] as u32);
+                (days * 86400, value.trim().parse::<f32>().unwrap())
+            })
+            .unzip();
+        let mut ts = TimeSeries::new(timestamps, values).unwrap();
+        assert_eq!(ts.regular_period(), Ok(86400));
+
+        // Weeks of daily counts
+        let m = 7;
+        let mp = ts.matrix_profile::<StompMatrixProfile>(m).unwrap();
+        let motifs = top_motifs(mp.get_profile(), mp.get_profile_idxs(), m, 3);
+        let timed = ts.motif_timestamps(&motifs);
+        for (motif, timed) in motifs.iter().zip(timed.iter()) {
+            assert_eq!(
+                timed.timestamp,
+                (days_from_civil(2014, 1, 1) + motif.idx as i64) * 86400
+            );
+            assert_eq!(
+                (timed.neighbour_timestamp - timed.timestamp) / 86400,
+                (motif.neighbour - motif.idx) as i64
+            );
+        }
+        let neighbours = ts.neighbour_timestamps(mp.get_profile_idxs());
+        assert_eq!(neighbours[0], Some(ts.timestamp(mp.get_profile_idxs()[0])));
+        assert_eq!(
+            ts.subsequence_span(0, m),
+            (ts.timestamp(0), ts.timestamp(6))
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Matrix Profile keeping the `k` nearest neighbours of each subsequence, useful for kNN based
+//! anomaly scores or to build motif graphs.
+use crate::{matrix_profile::NO_NEIGHBOUR, stomp::StompRows};
+use ndarray::prelude::*;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TopKMatrixProfile {
+    /// Distances to the `k` nearest neighbours of each subsequence, in ascending order.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::distance_matrix"))]
+    profiles: Array2<f32>,
+    /// Indices of the `k` nearest neighbours of each subsequence.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::index_matrix"))]
+    profiles_idxs: Array2<usize>,
+    /// Window size.
+    m: usize,
+    /// Nearby subsequences excluded as neighbours, at most this far apart.
+    exclusion_zone: usize,
+}
+
+impl TopKMatrixProfile {
+    /// Computes the `k` nearest neighbours of each subsequence using the STOMP algorithm.
+    ///
+    /// Neighbours are any subsequences outside the "exclusion zone", when there are less than `k`
+    /// of them the remaining entries have an infinite distance and [`NO_NEIGHBOUR`] as index.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Time series data
+    /// * `m` - Window size
+    /// * `k` - Number of neighbours to keep
+    pub fn calculate(x: Vec<f32>, m: usize, k: usize) -> Self {
+        assert!(k > 0, "At least one neighbour must be kept");
+        let mut rows = StompRows::new(x, m);
+        let n = rows.len();
+        let mut profiles = Array2::from_elem((n, k), f32::INFINITY);
+        let mut profiles_idxs = Array2::from_elem((n, k), NO_NEIGHBOUR);
+
+        while let Some((idx, distances)) = rows.next_row() {
+            for (i, &d) in distances.iter().enumerate() {
+                let mut profile = profiles.row_mut(i);
+                let mut profile_idxs = profiles_idxs.row_mut(i);
+                // Rows are processed in order, so equal distances keep the lowest index first
+                if let Some(pos) = profile.iter().position(|&o| d < o) {
+                    for j in ((pos + 1)..k).rev() {
+                        profile[j] = profile[j - 1];
+                        profile_idxs[j] = profile_idxs[j - 1];
+                    }
+                    profile[pos] = d;
+                    profile_idxs[pos] = idx;
+                }
+            }
+        }
+
+        profiles.mapv_inplace(f32::sqrt);
+
+        Self {
+            profiles,
+            profiles_idxs,
+            m,
+            exclusion_zone: (m as f32 / 4f32).ceil() as usize,
+        }
+    }
+
+    /// Distances to the nearest neighbours as an `n × k` array.
+    pub fn get_profiles(&self) -> &Array2<f32> {
+        &self.profiles
+    }
+
+    /// Indices of the nearest neighbours as an `n × k` array.
+    pub fn get_profiles_idxs(&self) -> &Array2<usize> {
+        &self.profiles_idxs
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.m
+    }
+
+    pub fn get_exclusion_zone(&self) -> usize {
+        self.exclusion_zone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix_profile::MatrixProfile, naive::NaiveMatrixProfile, stomp::StompMatrixProfile,
+        test_utils::random_data,
+    };
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_topk_first_neighbour() {
+        let x = random_data(100, 34);
+        let res = TopKMatrixProfile::calculate(x.clone(), 10, 3);
+        let expected = StompMatrixProfile::calculate(x, 10);
+        assert_eq!(res.get_profiles().dim(), (91, 3));
+        let profile = res.get_profiles().column(0).to_vec();
+        assert_relative_eq!(profile.as_slice(), expected.get_profile().as_slice());
+        assert_eq!(
+            res.get_profiles_idxs().column(0).to_vec().as_slice(),
+            expected.get_profile_idxs().as_slice()
+        );
+    }
+
+    #[test]
+    fn test_topk_naive() {
+        let x = random_data(60, 34);
+        let k = 4;
+        let res = TopKMatrixProfile::calculate(x.clone(), 8, k);
+        let naive = NaiveMatrixProfile::calculate_full_matrix(x, 8);
+        let matrix = naive.get_full_matrix().unwrap();
+
+        for (i, row) in matrix.genrows().into_iter().enumerate() {
+            let mut expected = row.iter().cloned().enumerate().collect::<Vec<_>>();
+            expected.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            let (expected_idxs, expected_profile): (Vec<_>, Vec<_>) =
+                expected.into_iter().take(k).unzip();
+            let profile = res.get_profiles().row(i).to_vec();
+            assert_relative_eq!(
+                profile.as_slice(),
+                expected_profile.as_slice(),
+                epsilon = 1e-3
+            );
+            assert_eq!(
+                res.get_profiles_idxs().row(i).to_vec(),
+                expected_idxs,
+                "row {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_topk_not_enough_neighbours() {
+        let x = random_data(12, 34);
+        let res = TopKMatrixProfile::calculate(x, 4, 8);
+        for row in res.get_profiles_idxs().genrows() {
+            assert_eq!(row[7], NO_NEIGHBOUR);
+        }
+        assert!(res.get_profiles().column(7).iter().all(|o| o.is_infinite()));
+    }
+}
@@ -1,6 +1,10 @@
+use crate::matrix_profile::NO_NEIGHBOUR;
 use ndarray::{prelude::*, Data, ScalarOperand};
 use num_traits::{Float, FromPrimitive};
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    io::{self, Read, Write},
+};
 
 /// Normalizes an array sing mean and std.
 pub fn normalize<T>(x: Array1<T>) -> Array1<T>
@@ -17,6 +21,106 @@ pub fn euclidean_distance<T: Float>(a: &Array1<T>, b: &Array1<T>) -> T {
     (a - b).mapv(|o| o.powi(2)).sum().sqrt()
 }
 
+/// Converts the z-normalized euclidean distance between two subsequences of length `m` into
+/// their Pearson correlation coefficient.
+pub fn distance_to_correlation(d: f32, m: usize) -> f32 {
+    1.0 - d.powi(2) / (2.0 * m as f32)
+}
+
+/// Converts the Pearson correlation coefficient between two subsequences of length `m` into
+/// their z-normalized euclidean distance.
+pub fn correlation_to_distance(rho: f32, m: usize) -> f32 {
+    (2.0 * m as f32 * (1.0 - rho)).max(0.0).sqrt()
+}
+
+/// Flags the subsequences of length `m` that don't contain missing (NaN or infinite) values.
+pub fn valid_subsequences(x: &Array1<f32>, m: usize) -> Vec<bool> {
+    let mut missing = 0usize;
+    let mut valid = Vec::with_capacity(x.len() + 1 - m);
+    for (i, o) in x.iter().enumerate() {
+        if !o.is_finite() {
+            missing += 1;
+        }
+        if i >= m && !x[i - m].is_finite() {
+            missing -= 1;
+        }
+        if i + 1 >= m {
+            valid.push(missing == 0);
+        }
+    }
+    valid
+}
+
+/// Flags the constant subsequences of length `m` (all their values equal), whose z-normalized
+/// distance is undefined, see [`constant_squared_distance`].
+pub fn constant_subsequences(x: &Array1<f32>, m: usize) -> Vec<bool> {
+    // Length of the run of equal values ending at each value
+    let mut run = 0usize;
+    let mut constant = Vec::with_capacity(x.len() + 1 - m);
+    for (i, o) in x.iter().enumerate() {
+        run = if i > 0 && x[i - 1] == *o { run + 1 } else { 1 };
+        if i + 1 >= m {
+            constant.push(run >= m);
+        }
+    }
+    constant
+}
+
+/// Squared distance between two subsequences of length `m` when any of them is constant, with
+/// the same convention as `stumpy`: zero between two constant subsequences and `m` (a distance
+/// of `sqrt(m)`) between a constant and a non-constant one. Returns `None` when none of them is
+/// constant.
+pub fn constant_squared_distance(a: bool, b: bool, m: usize) -> Option<f32> {
+    match (a, b) {
+        (true, true) => Some(0.0),
+        (true, false) | (false, true) => Some(m as f32),
+        (false, false) => None,
+    }
+}
+
+/// Replaces missing (NaN or infinite) values with zeros, so they don't propagate through
+/// rolling computations.
+pub fn fill_missing(x: &Array1<f32>) -> Array1<f32> {
+    x.mapv(|o| if o.is_finite() { o } else { 0.0 })
+}
+
+/// Writes an index as a little endian `u64`, [`NO_NEIGHBOUR`] is kept as `u64::MAX`.
+pub(crate) fn write_usize<W: Write>(w: &mut W, v: usize) -> io::Result<()> {
+    let v = if v == NO_NEIGHBOUR {
+        u64::MAX
+    } else {
+        v as u64
+    };
+    w.write_all(&v.to_le_bytes())
+}
+
+pub(crate) fn read_usize<R: Read>(r: &mut R) -> io::Result<usize> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    match u64::from_le_bytes(buf) {
+        u64::MAX => Ok(NO_NEIGHBOUR),
+        v => Ok(v as usize),
+    }
+}
+
+/// Writes the values as little endian `f32`s.
+pub(crate) fn write_f32s<W: Write>(w: &mut W, values: &[f32]) -> io::Result<()> {
+    values
+        .iter()
+        .try_for_each(|o| w.write_all(&o.to_le_bytes()))
+}
+
+/// Reads `n` little endian `f32`s.
+pub(crate) fn read_f32s<R: Read>(r: &mut R, n: usize) -> io::Result<Vec<f32>> {
+    let mut buf = [0u8; 4];
+    (0..n)
+        .map(|_| {
+            r.read_exact(&mut buf)?;
+            Ok(f32::from_le_bytes(buf))
+        })
+        .collect()
+}
+
 pub trait ArgminSkipZero<A, S>
 where
     S: Data<Elem = A>,
@@ -71,12 +175,40 @@ mod tests {
         assert_relative_eq!(res, 2.23, epsilon = 1e-2);
     }
 
+    #[test]
+    fn test_correlation_conversion() {
+        let m = 10;
+        assert_relative_eq!(distance_to_correlation(0.0, m), 1.0);
+        assert_relative_eq!(correlation_to_distance(1.0, m), 0.0);
+        assert_relative_eq!(correlation_to_distance(-1.0, m), (4.0 * m as f32).sqrt());
+        for &rho in [-0.9, -0.3, 0.0, 0.5, 0.99].iter() {
+            let d = correlation_to_distance(rho, m);
+            assert_relative_eq!(distance_to_correlation(d, m), rho, epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_valid_subsequences() {
+        let x = Array1::from(vec![0.0, 1.0, f32::NAN, 3.0, 4.0, 5.0, f32::INFINITY, 7.0]);
+        let valid = valid_subsequences(&x, 2);
+        assert_eq!(valid, vec![true, false, false, true, true, false, false]);
+        let filled = fill_missing(&x);
+        assert_eq!(filled[2], 0.0);
+        assert_eq!(filled[6], 0.0);
+        let x = Array1::from(vec![0.0, 1.0, 1.0, 1.0, 2.0, f32::NAN, 3.0, 3.0]);
+        let constant = constant_subsequences(&x, 3);
+        assert_eq!(constant, vec![false, true, false, false, false, false]);
+        assert_eq!(constant_squared_distance(true, true, 9), Some(0.0));
+        assert_eq!(constant_squared_distance(false, true, 9), Some(9.0));
+        assert_eq!(constant_squared_distance(false, false, 9), None);
+    }
+
     #[test]
     fn test_argmin() {
         let x = Array1::from(vec![0.0, 1.0, 2.0, 3.0, 4.0]);
         let idx = x.argmin_skipzero();
         assert_eq!(idx, 1);
-        let x = Array1::from(vec![std::f64::NAN, 0.0, 2.0, 3.0, 4.0]);
+        let x = Array1::from(vec![f64::NAN, 0.0, 2.0, 3.0, 4.0]);
         let idx = x.argmin_skipzero();
         assert_eq!(idx, 2);
     }
@@ -0,0 +1,76 @@
+//! Runs the `tile_worker` binary as the workers of a `Coordinator`, through its stdin/stdout and
+//! through a Unix socket.
+#![cfg(unix)]
+use matrix_profile::{datasets::random_walk, distributed::Coordinator, tiles::calculate_tiled};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+const WORKER: &str = env!("CARGO_BIN_EXE_tile_worker");
+
+/// Writes a random walk as raw `f32` values to a temporary file, returning its values and path.
+fn write_series(name: &str) -> (Vec<f32>, PathBuf) {
+    let x = random_walk(300, 34);
+    let path = std::env::temp_dir().join(format!("{}_{}.f32", name, std::process::id()));
+    let bytes = x.iter().flat_map(|o| o.to_ne_bytes()).collect::<Vec<_>>();
+    std::fs::write(&path, bytes).unwrap();
+    (x, path)
+}
+
+#[test]
+fn test_spawned_workers() {
+    let (x, path) = write_series("mp_spawned_workers");
+    let mut coordinator = Coordinator::spawn(&mut Command::new(WORKER), 2).unwrap();
+    let res = coordinator.run(&path, 10, 40).unwrap();
+    // Dropping the coordinator closes the workers input and waits for them to exit
+    drop(coordinator);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(res, calculate_tiled(&x, 10, 40));
+}
+
+fn wait_for(path: &Path) {
+    for _ in 0..500 {
+        if path.exists() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("The worker didn't create {:?}", path);
+}
+
+#[test]
+fn test_socket_workers() {
+    let (x, path) = write_series("mp_socket_workers");
+    let dir = std::env::temp_dir();
+    let sockets = (0..2)
+        .map(|i| dir.join(format!("mp_worker_{}_{}.sock", std::process::id(), i)))
+        .collect::<Vec<_>>();
+    let mut workers = sockets
+        .iter()
+        .map(|socket| {
+            Command::new(WORKER)
+                .arg("--socket")
+                .arg(socket)
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    sockets.iter().for_each(|o| wait_for(o));
+
+    // A listening worker serves several connections
+    for _ in 0..2 {
+        let res = Coordinator::connect(&sockets).unwrap().run(&path, 10, 40);
+        assert_eq!(res.unwrap(), calculate_tiled(&x, 10, 40));
+    }
+
+    for worker in workers.iter_mut() {
+        worker.kill().unwrap();
+        worker.wait().unwrap();
+    }
+    for path in sockets.iter().chain([path].iter()) {
+        std::fs::remove_file(path).unwrap();
+    }
+}